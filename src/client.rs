@@ -0,0 +1,201 @@
+use crate::driver::{BrowserOptions, Config, Driver};
+use crate::error::{KvError, Result};
+use crate::keystore::Credentials;
+use crate::observer::Observer;
+use crate::rate_limit::RateLimits;
+use crate::retry::RetryPolicy;
+use crate::selectors::Selectors;
+use crate::tasks::batch::SongOutcome;
+use crate::tasks::download_song::{DownloadOptions, DownloadReport};
+use crate::tasks::song_info::SongInfo;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// Downloads songs from your own code, with the credentials and settings handed to it rather
+/// than read from the environment, the keychain or the command line.
+///
+/// ```no_run
+/// use kv_downloader::client::KvClient;
+/// use kv_downloader::keystore::Credentials;
+/// use kv_downloader::tasks::download_song::DownloadOptions;
+///
+/// let client = KvClient::builder()
+///     .credentials(Credentials {
+///         user: "me@example.com".to_string(),
+///         password: "secret".to_string(),
+///     })
+///     .download_dir("/music/backing-tracks")
+///     .headless(true)
+///     .build()?;
+///
+/// let url = "https://www.karaoke-version.com/custombackingtrack/the-smashing-pumpkins/cherub-rock.html";
+/// let info = client.song_info(url)?;
+/// if info.purchased {
+///     let report = client.download_song(url, DownloadOptions::default())?;
+///     println!("downloaded {} tracks", report.downloaded.len());
+/// }
+/// # Ok::<(), kv_downloader::error::KvError>(())
+/// ```
+///
+/// The client signs in on first use. Like the command line, it restores and saves the session
+/// in the system keychain (unless [`KvClientBuilder::persist_session`] is off), and keeps track of finished tracks in the download directory so an
+/// interrupted download picks up where it left off.
+pub struct KvClient {
+    driver: Driver,
+    credentials: Credentials,
+    signed_in: AtomicBool,
+}
+
+impl KvClient {
+    pub fn builder() -> KvClientBuilder {
+        KvClientBuilder::default()
+    }
+
+    pub fn sign_in(&self) -> Result<()> {
+        self.driver
+            .sign_in(&self.credentials.user, &self.credentials.password)?;
+        self.signed_in.store(true, Ordering::SeqCst);
+        Ok(())
+    }
+
+    /// Reads the song page at `url` without downloading anything.
+    pub fn song_info(&self, url: &str) -> Result<SongInfo> {
+        self.ensure_signed_in()?;
+        self.driver.song_info(url)
+    }
+
+    /// Downloads the song at `url`. If some tracks fail, the error is a
+    /// [`DownloadError::TracksFailed`](crate::tasks::download_song::DownloadError::TracksFailed) carrying the report of what was saved all the same.
+    pub fn download_song(&self, url: &str, options: DownloadOptions) -> Result<DownloadReport> {
+        self.ensure_signed_in()?;
+        self.driver.download_song(url, options)
+    }
+
+    /// Downloads several songs, up to `jobs` at a time, each into a directory of its own. See
+    /// [`Driver::download_songs`].
+    pub fn download_songs(
+        &self,
+        urls: &[String],
+        options: &DownloadOptions,
+        jobs: usize,
+    ) -> Result<Vec<SongOutcome>> {
+        self.ensure_signed_in()?;
+        self.driver.download_songs(urls, options, jobs)
+    }
+
+    /// The underlying driver, for anything the client doesn't cover.
+    pub fn driver(&self) -> &Driver {
+        &self.driver
+    }
+
+    fn ensure_signed_in(&self) -> Result<()> {
+        if self.signed_in.load(Ordering::SeqCst) {
+            return Ok(());
+        }
+        self.sign_in()
+    }
+}
+
+/// Settings for a [`KvClient`]. Only the credentials are required; everything else defaults to
+/// what the command line would use without any flags, except that the browser is headless.
+pub struct KvClientBuilder {
+    config: Config,
+    credentials: Option<Credentials>,
+}
+
+impl Default for KvClientBuilder {
+    fn default() -> Self {
+        KvClientBuilder {
+            config: Config {
+                headless: true,
+                ..Config::default()
+            },
+            credentials: None,
+        }
+    }
+}
+
+impl KvClientBuilder {
+    /// The site to sign in to (default `www.karaoke-version.com`)
+    pub fn domain(mut self, domain: impl Into<String>) -> Self {
+        self.config.domain = domain.into();
+        self
+    }
+
+    pub fn credentials(mut self, credentials: Credentials) -> Self {
+        self.credentials = Some(credentials);
+        self
+    }
+
+    /// Where to save the tracks. The browser's default download folder if not set.
+    pub fn download_dir(mut self, dir: impl AsRef<Path>) -> Self {
+        self.config.download_path = Some(dir.as_ref().to_string_lossy().into_owned());
+        self
+    }
+
+    pub fn browser(mut self, options: BrowserOptions) -> Self {
+        self.config.browser = options;
+        self
+    }
+
+    pub fn headless(mut self, headless: bool) -> Self {
+        self.config.headless = headless;
+        self
+    }
+
+    /// Attach to a running Chrome through its DevTools websocket url instead of launching one.
+    pub fn connect(mut self, ws_url: impl Into<String>) -> Self {
+        self.config.connect = Some(ws_url.into());
+        self
+    }
+
+    pub fn selectors(mut self, selectors: Selectors) -> Self {
+        self.config.selectors = selectors;
+        self
+    }
+
+    pub fn retry(mut self, policy: RetryPolicy) -> Self {
+        self.config.retry = policy;
+        self
+    }
+
+    /// Pace track generation. `usage_file` keeps the usage across runs; in memory only if
+    /// `None`.
+    pub fn limits(mut self, limits: RateLimits, usage_file: Option<PathBuf>) -> Self {
+        self.config.limits = limits;
+        self.config.usage_file = usage_file;
+        self
+    }
+
+    /// Whether to restore the session from the system keychain and save it there after signing
+    /// in (default `true`). Turn it off where there is no keychain, i.e. in a container.
+    pub fn persist_session(mut self, persist: bool) -> Self {
+        self.config.persist_session = persist;
+        self
+    }
+
+    /// Save a screenshot and snapshot of the page here when something fails.
+    pub fn diagnostics_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.config.diagnostics_dir = Some(dir.into());
+        self
+    }
+
+    /// Tell `observer` about each step of the run as it happens. Can be called more than once.
+    pub fn observer(mut self, observer: Arc<dyn Observer>) -> Self {
+        self.config.observers.push(observer);
+        self
+    }
+
+    /// Launches (or attaches to) the browser.
+    pub fn build(self) -> Result<KvClient> {
+        let credentials = self
+            .credentials
+            .ok_or_else(|| KvError::Auth("No credentials given to the client".to_string()))?;
+        Ok(KvClient {
+            driver: Driver::new(self.config)?,
+            credentials,
+            signed_in: AtomicBool::new(false),
+        })
+    }
+}
@@ -0,0 +1,119 @@
+use std::path::PathBuf;
+
+use crate::{config_file::BrowserSection, driver};
+use anyhow::{anyhow, Result};
+use clap::Args;
+
+// Options for how the browser is launched or connected to, shared by the commands that drive it.
+#[derive(Debug, Args)]
+pub struct BrowserArgs {
+    #[arg(
+        short = 'H',
+        long,
+        help = "Set this flag to launch the browser headless."
+    )]
+    pub headless: bool,
+
+    #[arg(
+        long,
+        value_name = "WS_URL",
+        conflicts_with = "connect_port",
+        help = "Attach to a running Chrome via its DevTools websocket url (i.e. ws://127.0.0.1:9222/devtools/browser/...) instead of launching one"
+    )]
+    pub connect: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "PORT",
+        help = "Attach to a Chrome running locally with --remote-debugging-port=PORT"
+    )]
+    pub connect_port: Option<u16>,
+
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "The Chrome/Chromium binary to launch"
+    )]
+    pub chrome: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "DIR",
+        help = "Use a persistent browser profile directory"
+    )]
+    pub user_data_dir: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "URL",
+        help = "Route browser traffic through a proxy (i.e. http://host:3128 or socks5://host:1080)"
+    )]
+    pub proxy: Option<String>,
+
+    #[arg(
+        long,
+        help = "Disable the Chrome sandbox (needed when running as root, i.e. in Docker)"
+    )]
+    pub no_sandbox: bool,
+
+    #[arg(
+        long = "browser-arg",
+        value_name = "ARG",
+        allow_hyphen_values = true,
+        help = "Extra command line argument to pass to Chrome (can be repeated)"
+    )]
+    pub browser_args: Vec<String>,
+
+    #[arg(
+        long,
+        value_name = "WIDTHxHEIGHT",
+        value_parser = parse_window_size,
+        help = "Browser window size (default 1440x1200)"
+    )]
+    pub window_size: Option<(u32, u32)>,
+}
+
+impl BrowserArgs {
+    /// Resolves the DevTools websocket url to attach to, if any.
+    pub fn connect_url(&self) -> Result<Option<String>> {
+        match (&self.connect, self.connect_port) {
+            (Some(url), _) => Ok(Some(url.clone())),
+            (None, Some(port)) => Ok(Some(driver::websocket_url_for_port(port)?)),
+            (None, None) => Ok(None),
+        }
+    }
+
+    pub fn headless(&self, file: &BrowserSection) -> bool {
+        self.headless || file.headless.unwrap_or(false)
+    }
+
+    /// Merges the command line flags over the `[browser]` section of the config file.
+    pub fn launch_options(&self, file: &BrowserSection) -> driver::BrowserOptions {
+        let defaults = driver::BrowserOptions::default();
+
+        let mut args = file.args.clone();
+        args.extend(self.browser_args.iter().cloned());
+
+        driver::BrowserOptions {
+            executable: self.chrome.clone().or_else(|| file.executable.clone()),
+            user_data_dir: self
+                .user_data_dir
+                .clone()
+                .or_else(|| file.user_data_dir.clone()),
+            proxy: self.proxy.clone().or_else(|| file.proxy.clone()),
+            sandbox: !self.no_sandbox && file.sandbox.unwrap_or(defaults.sandbox),
+            args,
+            window_size: self
+                .window_size
+                .or(file.window_size)
+                .unwrap_or(defaults.window_size),
+        }
+    }
+}
+
+fn parse_window_size(value: &str) -> Result<(u32, u32)> {
+    let (width, height) = value
+        .split_once('x')
+        .ok_or_else(|| anyhow!("expected WIDTHxHEIGHT, i.e. 1280x800"))?;
+    Ok((width.trim().parse()?, height.trim().parse()?))
+}
@@ -0,0 +1,98 @@
+use super::browser::BrowserArgs;
+use super::download::{extract_domain_from_url, load_credentials};
+use crate::{
+    config_file::ConfigFile,
+    driver,
+    selectors::Selectors,
+    tasks::doctor::{DoctorReport, SelectorCheck},
+};
+use anyhow::{anyhow, Result};
+use clap::Args;
+
+#[derive(Debug, Args)]
+pub struct DoctorArgs {
+    /// A song you have purchased
+    song_url: String,
+
+    #[command(flatten)]
+    browser: BrowserArgs,
+}
+
+pub fn run(args: DoctorArgs, config_file: &ConfigFile, selectors: Selectors) -> Result<()> {
+    let credentials = load_credentials()?;
+
+    let config = driver::Config {
+        domain: extract_domain_from_url(&args.song_url)
+            .ok_or_else(|| anyhow!("missing domain from url: {}", args.song_url))?,
+        headless: args.browser.headless(&config_file.browser),
+        connect: args.browser.connect_url()?,
+        browser: args.browser.launch_options(&config_file.browser),
+        selectors,
+        observers: vec![],
+        ..Default::default()
+    };
+    let driver = driver::Driver::new(config)?;
+
+    driver.sign_in(&credentials.user, &credentials.password)?;
+
+    let report = driver.check_selectors(&args.song_url)?;
+    print_report(&report);
+
+    if !report.is_ok() {
+        return Err(anyhow!(
+            "Some checks failed. If the selectors above look wrong, the site has probably changed."
+        ));
+    }
+
+    Ok(())
+}
+
+fn print_report(report: &DoctorReport) {
+    println!();
+    println!("Song page:");
+    println!("  {} signed in", mark(report.signed_in));
+    for check in &report.song_page {
+        print_check(check);
+    }
+    println!(
+        "  {} {} track names, {} solo buttons",
+        mark(report.track_names > 0 && report.track_names == report.solo_buttons),
+        report.track_names,
+        report.solo_buttons
+    );
+
+    println!();
+    println!("Login page:");
+    for check in &report.login_page {
+        print_check(check);
+    }
+
+    println!();
+    println!("Not checked (only shown once a track is soloed or a download is generated):");
+    println!(
+        "  - pressed solo and mute buttons, download modal, download link, modal close button"
+    );
+    println!();
+}
+
+fn print_check(check: &SelectorCheck) {
+    let matches = match check.matches {
+        Some(n) => format!("{} matches", n),
+        None => "invalid selector".to_string(),
+    };
+    println!(
+        "  {} {:<20} {:<14} {}",
+        mark(check.is_ok()),
+        check.name,
+        matches,
+        check.selector
+    );
+}
+
+fn mark(ok: bool) -> &'static str {
+    if ok {
+        "✓"
+    } else {
+        "✗"
+    }
+}
@@ -1,24 +1,46 @@
-use std::{env, thread::sleep, time::Duration};
+use std::{
+    env, fs,
+    io::{self, IsTerminal},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
 
+use super::browser::BrowserArgs;
+use super::limits::LimitArgs;
+use super::retry::RetryArgs;
 use crate::{
+    config_file::ConfigFile,
     driver,
+    error::KvError,
+    events::EventStream,
     keystore::{self, Credentials},
-    tasks,
+    links,
+    observer::{LogObserver, Observer},
+    progress_view::ProgressView,
+    selectors::Selectors,
+    tasks::{
+        self,
+        download_song::{DownloadError, DownloadMode},
+        dry_run::DownloadPlan,
+    },
 };
 use anyhow::{anyhow, Result};
-use clap::{arg, command, Args};
+use clap::{Args, ValueEnum};
 
 #[derive(Debug, Args)]
 #[command(flatten_help = true)]
 pub struct DownloadArgs {
-    song_url: String,
+    #[arg(required = true, value_name = "SONG_URL")]
+    song_urls: Vec<String>,
 
-    #[arg(
-        short = 'H',
-        long,
-        help = "Set this flag to launch the browser headless."
-    )]
-    headless: bool,
+    #[command(flatten)]
+    browser: BrowserArgs,
+
+    #[command(flatten)]
+    retry: RetryArgs,
+
+    #[command(flatten)]
+    limits: LimitArgs,
 
     #[arg(short, long)]
     download_path: Option<String>,
@@ -36,69 +58,383 @@ pub struct DownloadArgs {
     #[arg(short, long, help = "Whether to count in an intro for all tracks")]
     count_in: bool,
 
+    #[arg(
+        short,
+        long,
+        default_value = "1",
+        value_parser = clap::value_parser!(u16).range(1..),
+        help = "How many songs to download at the same time when given several"
+    )]
+    jobs: u16,
+
+    #[arg(
+        long,
+        help = "Fetch the tracks over HTTP with the browser's cookies instead of letting the browser save them, resuming interrupted transfers"
+    )]
+    direct: bool,
+
+    #[arg(
+        long,
+        value_name = "FILE",
+        conflicts_with = "direct",
+        help = "Generate every track but write its download link, with the cookies it needs, to this file instead of downloading it"
+    )]
+    links_only: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_enum,
+        default_value = "json",
+        requires = "links_only",
+        help = "Format of the --links-only file"
+    )]
+    links_format: LinksFormat,
+
+    #[arg(
+        long,
+        value_enum,
+        default_value = "human",
+        help = "How to report progress. `json` writes one JSON event per line to stdout and the logs to stderr"
+    )]
+    output: OutputFormat,
+
+    #[arg(
+        long,
+        help = "Fail straight away if the site asks to verify you are human, instead of opening a browser window to complete the check in"
+    )]
+    no_interactive: bool,
+
     #[arg(long, help = "Force restart, ignoring any previous download progress")]
     force_restart: bool,
+
+    #[arg(
+        long,
+        help = "Sign in and read each song page, then print which tracks would be downloaded and where, without downloading anything"
+    )]
+    dry_run: bool,
+
+    #[arg(
+        long,
+        value_name = "DIR",
+        default_value = "kv-diagnostics",
+        help = "Where to save a screenshot and snapshot of the page when something fails"
+    )]
+    diagnostics_dir: PathBuf,
+
+    #[arg(
+        long,
+        value_name = "DIR",
+        help = "Record a screencast of the run and save it as an animated GIF in this directory"
+    )]
+    record: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "FILE",
+        help = "Record the network traffic of the run into a HAR file, with cookies and passwords redacted"
+    )]
+    har: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+enum OutputFormat {
+    Human,
+    /// Newline-delimited JSON events
+    Json,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum LinksFormat {
+    Json,
+    /// An input file for `aria2c -i`
+    Aria2,
+}
+
+impl DownloadArgs {
+    /// How many songs were asked for
+    pub fn song_count(&self) -> usize {
+        self.song_urls.len()
+    }
+
+    /// Whether stdout is taken by the JSON event stream
+    pub fn json_output(&self) -> bool {
+        self.output == OutputFormat::Json
+    }
+
+    /// Whether a "verify you are human" check may be handed over to the user: not when asked
+    /// not to, nor when nobody is likely to be watching, i.e. output is piped or JSON
+    fn interactive(&self) -> bool {
+        !self.no_interactive
+            && !self.json_output()
+            && io::stdin().is_terminal()
+            && io::stdout().is_terminal()
+    }
+
+    /// Whether only the plan is printed, without downloading anything
+    pub fn dry_run(&self) -> bool {
+        self.dry_run
+    }
 }
 
 pub struct Download {}
 
 impl Download {
-    pub fn run(args: DownloadArgs) -> Result<()> {
-        Download::start_download(args)
+    pub fn run(
+        args: DownloadArgs,
+        config_file: &ConfigFile,
+        selectors: Selectors,
+        progress_view: Option<Arc<ProgressView>>,
+    ) -> Result<()> {
+        Download::start_download(args, config_file, selectors, progress_view)
     }
 
-    fn start_download(args: DownloadArgs) -> Result<()> {
-        let credentials = credentials_from_env().unwrap_or(
-            keystore::Keystore::get_credentials().map_err(|e| {
-                tracing::error!("credential error: {}", e);
-                anyhow!("Must call `kv-downloader auth` first")
-            })?,
-        );
+    fn start_download(
+        args: DownloadArgs,
+        config_file: &ConfigFile,
+        selectors: Selectors,
+        progress_view: Option<Arc<ProgressView>>,
+    ) -> Result<()> {
+        let credentials = load_credentials()?;
 
         tracing::debug!(args = format!("cli args: {:?}", args));
 
+        // the logs are filtered down to warnings while the view is shown
+        let mut observers: Vec<Arc<dyn Observer>> = vec![Arc::new(LogObserver)];
+        if let Some(view) = &progress_view {
+            observers.push(view.clone());
+        }
+        // a dry run prints its plan to stdout instead
+        if args.json_output() && !args.dry_run {
+            observers.push(Arc::new(EventStream::stdout()));
+        }
+
+        // every song is downloaded from the site we sign in to, so the first one decides
+        let first_url = &args.song_urls[0];
         let config = driver::Config {
-            domain: extract_domain_from_url(&args.song_url).expect("missing domain from url"),
-            headless: args.headless,
-            download_path: args.download_path,
+            domain: extract_domain_from_url(first_url)
+                .ok_or_else(|| anyhow!("missing domain from url: {}", first_url))?,
+            headless: args.browser.headless(&config_file.browser),
+            download_path: args.download_path.clone(),
+            connect: args.browser.connect_url()?,
+            browser: args.browser.launch_options(&config_file.browser),
+            selectors,
+            diagnostics_dir: Some(args.diagnostics_dir.clone()),
+            record_dir: args.record.clone(),
+            har_path: args.har.clone(),
+            interactive: args.interactive(),
+            persist_session: true,
+            retry: args.retry.policy(&config_file.retry),
+            limits: args.limits.limits(&config_file.limits),
+            usage_file: args.limits.usage_file(&config_file.limits),
+            observers,
         };
-        let driver = driver::Driver::new(config);
-
-        // Handle resume/restart logic
-        if args.force_restart {
-            tracing::info!("Force restart requested, clearing previous progress");
-            driver.progress.clear()?;
-        } else if driver.progress.is_same_url(&args.song_url)? {
-            let completed = driver.progress.get_completed_tracks()?;
-            if !completed.is_empty() {
-                tracing::info!(
-                    "Resuming previous download. Already completed {} tracks:",
-                    completed.len()
-                );
-                for track in &completed {
-                    tracing::info!("  ✓ {}", track);
-                }
-            }
-        } else if !driver.progress.get_completed_tracks()?.is_empty() {
-            // Different URL, clear the old progress
-            tracing::info!("Different song detected, clearing previous progress");
-            driver.progress.clear()?;
+        let driver = driver::Driver::new(config)?;
+
+        let result = Download::download_with_driver(&driver, &args, credentials);
+        if let Some(view) = &progress_view {
+            view.finish();
+        }
+        if let Err(e) = driver.finish() {
+            tracing::warn!("Unable to save the recordings: {}", e);
         }
+        result
+    }
 
+    fn download_with_driver(
+        driver: &driver::Driver,
+        args: &DownloadArgs,
+        credentials: Credentials,
+    ) -> Result<()> {
         driver.sign_in(&credentials.user, &credentials.password)?;
 
         let download_options = tasks::download_song::DownloadOptions {
             count_in: args.count_in,
             transpose: args.transpose.unwrap_or(0),
+            mode: if args.links_only.is_some() {
+                DownloadMode::LinksOnly
+            } else if args.direct {
+                DownloadMode::Direct
+            } else {
+                DownloadMode::Browser
+            },
+            force_restart: args.force_restart,
+        };
+
+        if args.dry_run {
+            return Download::dry_run(driver, args, &download_options);
+        }
+
+        if let [song_url] = args.song_urls.as_slice() {
+            let track_links = driver.download_song(song_url, download_options)?.links;
+            if let Some(path) = &args.links_only {
+                let contents = match args.links_format {
+                    LinksFormat::Json => links::to_json(song_url, &track_links)?,
+                    LinksFormat::Aria2 => {
+                        links::to_aria2(&track_links, args.download_path.as_deref())
+                    }
+                };
+                fs::write(path, contents)?;
+                tracing::info!("Wrote {} links to {}", track_links.len(), path.display());
+            }
+            return Ok(());
+        }
+
+        let outcomes =
+            driver.download_songs(&args.song_urls, &download_options, args.jobs as usize)?;
+
+        let mut songs = vec![];
+        let mut failed = vec![];
+        for outcome in outcomes {
+            match outcome.result {
+                Ok(report) => songs.push(links::SongLinks {
+                    song: outcome.url,
+                    dir: outcome.download_path,
+                    tracks: report.links,
+                }),
+                Err(_) => failed.push(outcome.url),
+            }
+        }
+        tracing::info!(
+            "Downloaded {} of {} songs",
+            songs.len(),
+            args.song_urls.len()
+        );
+
+        if let Some(path) = &args.links_only {
+            let contents = match args.links_format {
+                LinksFormat::Json => links::songs_to_json(&songs)?,
+                LinksFormat::Aria2 => songs
+                    .iter()
+                    .map(|song| links::to_aria2(&song.tracks, song.dir.to_str()))
+                    .collect(),
+            };
+            fs::write(path, contents)?;
+            tracing::info!(
+                "Wrote links for {} songs to {}",
+                songs.len(),
+                path.display()
+            );
+        }
+
+        if !failed.is_empty() {
+            for url in &failed {
+                tracing::error!("Failed: {}", url);
+            }
+            return Err(KvError::from(DownloadError::SongsFailed(failed)).into());
+        }
+        Ok(())
+    }
+
+    /// Prints what downloading would do, one JSON object per song with `--output json`. Fails
+    /// like the download would when a song can't be downloaded.
+    fn dry_run(
+        driver: &driver::Driver,
+        args: &DownloadArgs,
+        options: &tasks::download_song::DownloadOptions,
+    ) -> Result<()> {
+        let show = |plan: &DownloadPlan| -> Result<()> {
+            if args.json_output() {
+                println!("{}", serde_json::to_string(plan)?);
+            } else {
+                print_plan(plan, args.links_only.as_deref());
+            }
+            Ok(())
         };
-        driver.download_song(&args.song_url, download_options)?;
 
-        sleep(Duration::from_secs(10));
+        if let [song_url] = args.song_urls.as_slice() {
+            let plan = driver.plan_download(song_url, options)?;
+            show(&plan)?;
+            if !plan.song.purchased {
+                return Err(KvError::from(DownloadError::NotPurchased).into());
+            }
+            return Ok(());
+        }
 
+        let plans = driver.plan_downloads(&args.song_urls, options)?;
+        let mut failed = vec![];
+        for (url, plan) in args.song_urls.iter().zip(plans) {
+            match plan {
+                Ok(plan) => {
+                    show(&plan)?;
+                    if !plan.song.purchased {
+                        failed.push(url.clone());
+                    }
+                }
+                Err(e) => {
+                    tracing::error!("Unable to read {}: {}", url, e);
+                    failed.push(url.clone());
+                }
+            }
+        }
+        if !failed.is_empty() {
+            return Err(KvError::from(DownloadError::SongsFailed(failed)).into());
+        }
         Ok(())
     }
 }
 
+fn print_plan(plan: &DownloadPlan, links_file: Option<&Path>) {
+    let song = &plan.song;
+    println!();
+    println!("{}", song.title.as_deref().unwrap_or(&song.url));
+    println!("  {}", song.url);
+    if !song.purchased {
+        println!("  ✗ not purchased, nothing can be downloaded");
+        return;
+    }
+    match song.pitch {
+        Some(pitch) if plan.changes_pitch() => {
+            println!(
+                "  pitch would be changed from {} to {}",
+                pitch, plan.transpose
+            )
+        }
+        Some(pitch) => println!("  pitch stays at {}", pitch),
+        None => println!("  pitch would be set to {}", plan.transpose),
+    }
+    if plan.count_in {
+        println!("  with a count-in");
+    }
+
+    let action = match (plan.mode, &plan.download_dir) {
+        (DownloadMode::LinksOnly, _) => format!(
+            "links for {} tracks would be written to {}",
+            plan.tracks.len(),
+            links_file.map_or("the links file".into(), Path::to_string_lossy)
+        ),
+        (_, Some(dir)) => format!(
+            "{} tracks would be downloaded to {}",
+            plan.tracks.len(),
+            dir.display()
+        ),
+        (_, None) => format!(
+            "{} tracks would be downloaded to the browser's download folder",
+            plan.tracks.len()
+        ),
+    };
+    println!("  {}:", action);
+    for track in &plan.tracks {
+        println!("    - {}", track);
+    }
+    if !plan.skipped.is_empty() {
+        println!("  already downloaded, skipped:");
+        for track in &plan.skipped {
+            println!("    ✓ {}", track);
+        }
+    }
+}
+
+/// Credentials from the environment (or `.env`), falling back to the keychain.
+pub(super) fn load_credentials() -> Result<Credentials, KvError> {
+    match credentials_from_env() {
+        Some(credentials) => Ok(credentials),
+        None => keystore::Keystore::get_credentials().map_err(|e| {
+            tracing::error!("credential error: {}", e);
+            KvError::Auth("Must call `kv-downloader auth` first".to_string())
+        }),
+    }
+}
+
 fn credentials_from_env() -> Option<Credentials> {
     env::var("KV_USERNAME")
         .and_then(|user| match env::var("KV_PASSWORD") {
@@ -108,7 +444,7 @@ fn credentials_from_env() -> Option<Credentials> {
         .ok()
 }
 
-fn extract_domain_from_url(url: &str) -> Option<String> {
+pub(super) fn extract_domain_from_url(url: &str) -> Option<String> {
     url::Url::parse(url)
         .ok()
         .and_then(|url| url.host_str().map(|h| h.to_string()))
@@ -0,0 +1,49 @@
+use std::{num::NonZeroU32, path::PathBuf, time::Duration};
+
+use crate::{
+    config_file::LimitsSection,
+    rate_limit::{RateLimiter, RateLimits},
+};
+use clap::Args;
+
+// How hard the site may be used. Usage is counted across runs, so the caps hold for batches too.
+#[derive(Debug, Args)]
+pub struct LimitArgs {
+    #[arg(
+        long,
+        value_name = "SECS",
+        help = "Minimum time between generating two tracks"
+    )]
+    pub min_delay: Option<u64>,
+
+    #[arg(
+        long,
+        value_name = "N",
+        help = "Generate at most N tracks per hour, pausing once the limit is reached"
+    )]
+    pub max_per_hour: Option<NonZeroU32>,
+
+    #[arg(
+        long,
+        value_name = "N",
+        help = "Generate at most N tracks per day, pausing once the limit is reached"
+    )]
+    pub max_per_day: Option<NonZeroU32>,
+}
+
+impl LimitArgs {
+    /// Merges the command line flags over the `[limits]` section of the config file.
+    pub fn limits(&self, file: &LimitsSection) -> RateLimits {
+        RateLimits {
+            min_delay: Duration::from_secs(self.min_delay.or(file.min_delay).unwrap_or(0)),
+            per_hour: self.max_per_hour.or(file.per_hour),
+            per_day: self.max_per_day.or(file.per_day),
+        }
+    }
+
+    pub fn usage_file(&self, file: &LimitsSection) -> Option<PathBuf> {
+        file.usage_file
+            .clone()
+            .or_else(RateLimiter::default_usage_file)
+    }
+}
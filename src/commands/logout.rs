@@ -2,5 +2,5 @@ use crate::keystore;
 use anyhow::Result;
 
 pub fn run() -> Result<()> {
-    keystore::Keystore::logout()
+    Ok(keystore::Keystore::logout()?)
 }
@@ -1,6 +1,11 @@
 pub mod auth;
+mod browser;
+pub mod doctor;
 mod download;
+mod limits;
 pub mod logout;
+mod retry;
+pub mod session;
 
 pub use download::Download;
 pub use download::DownloadArgs;
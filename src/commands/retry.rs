@@ -0,0 +1,73 @@
+use std::time::Duration;
+
+use crate::{config_file::RetrySection, retry::RetryPolicy};
+use clap::Args;
+
+// How failed tracks are retried. Anything not given here or in the config file keeps its default.
+#[derive(Debug, Args)]
+pub struct RetryArgs {
+    #[arg(
+        long,
+        value_name = "N",
+        value_parser = clap::value_parser!(u32).range(1..),
+        help = "Attempts per track before giving up on it (default 3)"
+    )]
+    pub max_attempts: Option<u32>,
+
+    #[arg(
+        long,
+        value_name = "SECS",
+        help = "Wait after a failed attempt, doubling with each further failure (default 5)"
+    )]
+    pub retry_backoff: Option<u64>,
+
+    #[arg(
+        long,
+        value_name = "SECS",
+        help = "How long to wait for the download modal on the first attempt (default 60)"
+    )]
+    pub modal_timeout: Option<u64>,
+
+    #[arg(
+        long,
+        value_name = "SECS",
+        help = "How long a single track may take to download (default 300)"
+    )]
+    pub download_timeout: Option<u64>,
+}
+
+impl RetryArgs {
+    /// Merges the command line flags over the `[retry]` section of the config file.
+    pub fn policy(&self, file: &RetrySection) -> RetryPolicy {
+        let defaults = RetryPolicy::default();
+        let secs = |cli: Option<u64>, file: Option<u64>, default: Duration| {
+            cli.or(file).map(Duration::from_secs).unwrap_or(default)
+        };
+
+        RetryPolicy {
+            max_attempts: self
+                .max_attempts
+                .or(file.max_attempts)
+                .unwrap_or(defaults.max_attempts),
+            backoff: secs(self.retry_backoff, file.backoff, defaults.backoff),
+            max_backoff: secs(None, file.max_backoff, defaults.max_backoff),
+            jitter: file.jitter.unwrap_or(defaults.jitter),
+            modal_timeout: secs(
+                self.modal_timeout,
+                file.modal_timeout,
+                defaults.modal_timeout,
+            ),
+            modal_timeout_step: secs(None, file.modal_timeout_step, defaults.modal_timeout_step),
+            download_start_timeout: secs(
+                None,
+                file.download_start_timeout,
+                defaults.download_start_timeout,
+            ),
+            download_timeout: secs(
+                self.download_timeout,
+                file.download_timeout,
+                defaults.download_timeout,
+            ),
+        }
+    }
+}
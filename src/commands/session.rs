@@ -0,0 +1,90 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    cookies_txt,
+    keystore::Keystore,
+    session::{cookie_matches_domain, Session},
+};
+use anyhow::{anyhow, Result};
+use clap::Subcommand;
+
+#[derive(Debug, Subcommand)]
+pub enum SessionCommand {
+    /// Seed the saved session from a Netscape cookies.txt file exported from a browser
+    Import {
+        path: PathBuf,
+
+        #[arg(
+            long,
+            default_value = "www.karaoke-version.com",
+            help = "Only import cookies that apply to this site"
+        )]
+        domain: String,
+    },
+    /// Write the saved session cookies in Netscape cookies.txt format
+    Export {
+        #[arg(short, long, help = "Write to a file instead of stdout")]
+        output: Option<PathBuf>,
+    },
+}
+
+pub fn run(command: SessionCommand) -> Result<()> {
+    match command {
+        SessionCommand::Import { path, domain } => import(&path, &domain),
+        SessionCommand::Export { output } => export(output),
+    }
+}
+
+fn import(path: &Path, domain: &str) -> Result<()> {
+    let contents = fs::read_to_string(path)?;
+    let cookies: Vec<_> = cookies_txt::parse(&contents)?
+        .into_iter()
+        .filter(|c| cookie_matches_domain(&c.domain, domain))
+        .collect();
+
+    if cookies.is_empty() {
+        return Err(anyhow!(
+            "No cookies for {} found in {}",
+            domain,
+            path.display()
+        ));
+    }
+
+    // keep any local storage from a previous sign-in, the cookies are what matter most
+    let previous = Keystore::get_session().unwrap_or_default();
+    let session = Session {
+        cookies,
+        local_storage: previous.local_storage,
+        saved_at: previous.saved_at,
+    }
+    .without_expired();
+
+    tracing::info!("Imported {} cookies for {}", session.cookies.len(), domain);
+    Ok(Keystore::set_session(&session)?)
+}
+
+fn export(output: Option<PathBuf>) -> Result<()> {
+    let session = Keystore::get_session()
+        .map_err(|_| {
+            anyhow!("No saved session. Run `kv-downloader download` or `session import` first")
+        })?
+        .without_expired();
+
+    let contents = cookies_txt::format(&session.cookies);
+    match output {
+        Some(path) => {
+            fs::write(&path, contents)?;
+            tracing::info!(
+                "Exported {} cookies to {}",
+                session.cookies.len(),
+                path.display()
+            );
+        }
+        None => print!("{}", contents),
+    }
+
+    Ok(())
+}
@@ -0,0 +1,103 @@
+use anyhow::{anyhow, Result};
+use serde::Deserialize;
+use std::fs;
+use std::num::NonZeroU32;
+use std::path::{Path, PathBuf};
+
+/// Settings read from `config.toml`. Anything passed on the command line takes precedence.
+///
+/// ```toml
+/// [browser]
+/// executable = "/usr/bin/chromium"
+/// user_data_dir = "/home/me/.kv-profile"
+/// proxy = "socks5://127.0.0.1:1080"
+/// sandbox = false
+/// args = ["--lang=en-US"]
+/// window_size = [1280, 800]
+///
+/// [retry]
+/// max_attempts = 5
+/// backoff = 10
+/// modal_timeout = 90
+///
+/// [limits]
+/// min_delay = 10
+/// per_hour = 60
+/// ```
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ConfigFile {
+    pub browser: BrowserSection,
+    pub retry: RetrySection,
+    pub limits: LimitsSection,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BrowserSection {
+    pub headless: Option<bool>,
+    pub executable: Option<PathBuf>,
+    pub user_data_dir: Option<PathBuf>,
+    pub proxy: Option<String>,
+    pub sandbox: Option<bool>,
+    pub args: Vec<String>,
+    pub window_size: Option<(u32, u32)>,
+}
+
+/// Durations are in seconds.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RetrySection {
+    pub max_attempts: Option<u32>,
+    pub backoff: Option<u64>,
+    pub max_backoff: Option<u64>,
+    pub jitter: Option<f64>,
+    pub modal_timeout: Option<u64>,
+    pub modal_timeout_step: Option<u64>,
+    pub download_start_timeout: Option<u64>,
+    pub download_timeout: Option<u64>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LimitsSection {
+    /// Seconds between two track generations
+    pub min_delay: Option<u64>,
+    pub per_hour: Option<NonZeroU32>,
+    pub per_day: Option<NonZeroU32>,
+    pub usage_file: Option<PathBuf>,
+}
+
+impl ConfigFile {
+    /// Loads the config file at `path`, or from the default location if no path is given.
+    /// A missing file at the default location is not an error.
+    pub fn load(path: Option<&Path>) -> Result<Self> {
+        let (path, required) = match path {
+            Some(path) => (path.to_path_buf(), true),
+            None => match Self::default_path() {
+                Some(path) => (path, false),
+                None => return Ok(Self::default()),
+            },
+        };
+
+        if !path.exists() {
+            if required {
+                return Err(anyhow!("Config file not found: {}", path.display()));
+            }
+            return Ok(Self::default());
+        }
+
+        tracing::debug!("Loading config from {}", path.display());
+        Self::parse(&fs::read_to_string(&path)?)
+            .map_err(|e| anyhow!("Invalid config file {}: {}", path.display(), e))
+    }
+
+    pub fn parse(contents: &str) -> Result<Self> {
+        Ok(toml::from_str(contents)?)
+    }
+
+    /// `~/.config/kv-downloader/config.toml` on Linux, and the platform equivalent elsewhere.
+    pub fn default_path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("kv-downloader").join("config.toml"))
+    }
+}
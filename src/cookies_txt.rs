@@ -0,0 +1,89 @@
+//! Reading and writing the Netscape `cookies.txt` format used by browser extensions, curl,
+//! wget, yt-dlp and friends.
+
+use crate::session::SessionCookie;
+use anyhow::{anyhow, Result};
+
+const HEADER: &str = "# Netscape HTTP Cookie File";
+const HTTP_ONLY_PREFIX: &str = "#HttpOnly_";
+
+pub fn parse(input: &str) -> Result<Vec<SessionCookie>> {
+    let mut cookies = vec![];
+
+    for (index, line) in input.lines().enumerate() {
+        let line = line.trim_end_matches('\r');
+
+        // http-only cookies are written as comments with a special prefix
+        let (line, http_only) = match line.strip_prefix(HTTP_ONLY_PREFIX) {
+            Some(rest) => (rest, true),
+            None => (line, false),
+        };
+
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let fields: Vec<&str> = line.split('\t').collect();
+        if fields.len() != 7 {
+            return Err(anyhow!(
+                "line {}: expected 7 tab-separated fields, found {}",
+                index + 1,
+                fields.len()
+            ));
+        }
+
+        let expires: i64 = fields[4]
+            .parse()
+            .map_err(|_| anyhow!("line {}: invalid expiry '{}'", index + 1, fields[4]))?;
+
+        cookies.push(SessionCookie {
+            domain: fields[0].to_string(),
+            path: fields[2].to_string(),
+            secure: parse_bool(fields[3]),
+            // 0 marks a session cookie
+            expires: if expires > 0 { expires as f64 } else { -1.0 },
+            name: fields[5].to_string(),
+            value: fields[6].to_string(),
+            http_only,
+            same_site: None,
+        });
+    }
+
+    Ok(cookies)
+}
+
+pub fn format(cookies: &[SessionCookie]) -> String {
+    let mut out = format!("{}\n# Exported by kv-downloader\n\n", HEADER);
+
+    for c in cookies {
+        let prefix = if c.http_only { HTTP_ONLY_PREFIX } else { "" };
+        let include_subdomains = c.domain.starts_with('.');
+        let expires = if c.expires > 0.0 { c.expires as i64 } else { 0 };
+
+        out.push_str(&format!(
+            "{}{}\t{}\t{}\t{}\t{}\t{}\t{}\n",
+            prefix,
+            c.domain,
+            format_bool(include_subdomains),
+            c.path,
+            format_bool(c.secure),
+            expires,
+            c.name,
+            c.value
+        ));
+    }
+
+    out
+}
+
+fn parse_bool(value: &str) -> bool {
+    value.eq_ignore_ascii_case("true")
+}
+
+fn format_bool(value: bool) -> &'static str {
+    if value {
+        "TRUE"
+    } else {
+        "FALSE"
+    }
+}
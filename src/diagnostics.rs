@@ -0,0 +1,58 @@
+use crate::error::{KvError, Result};
+use base64::prelude::{Engine, BASE64_STANDARD};
+use headless_chrome::protocol::cdp::Page;
+use headless_chrome::Tab;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Saves a full-page screenshot and the current DOM of `tab` into a new timestamped folder
+/// under `dir`, returning the folder's path.
+pub fn capture(tab: &Tab, dir: &Path, label: &str, error: &KvError) -> Result<PathBuf> {
+    let folder = dir.join(format!(
+        "{}-{}",
+        chrono::Local::now().format("%Y%m%d-%H%M%S%.3f"),
+        label
+    ));
+    fs::create_dir_all(&folder)?;
+
+    fs::write(
+        folder.join("error.txt"),
+        format!("url: {}\nerror: {}\n", tab.get_url(), error),
+    )?;
+
+    let html = tab
+        .evaluate("document.documentElement.outerHTML", false)?
+        .value
+        .and_then(|v| v.as_str().map(str::to_string))
+        .unwrap_or_default();
+    fs::write(folder.join("page.html"), html)?;
+
+    fs::write(folder.join("screenshot.png"), full_page_screenshot(tab)?)?;
+
+    Ok(folder)
+}
+
+/// A PNG of the whole page, not only the part that fits in the window.
+fn full_page_screenshot(tab: &Tab) -> Result<Vec<u8>> {
+    let size = tab
+        .call_method(Page::GetLayoutMetrics(None))?
+        .css_content_size;
+    let data = tab
+        .call_method(Page::CaptureScreenshot {
+            format: Some(Page::CaptureScreenshotFormatOption::Png),
+            quality: None,
+            clip: Some(Page::Viewport {
+                x: 0.0,
+                y: 0.0,
+                width: size.width,
+                height: size.height,
+                scale: 1.0,
+            }),
+            from_surface: Some(true),
+            capture_beyond_viewport: Some(true),
+        })?
+        .data;
+    BASE64_STANDARD
+        .decode(data)
+        .map_err(|e| KvError::Browser(e.into()))
+}
@@ -1,4 +1,4 @@
-use anyhow::Result;
+use crate::error::Result;
 use serde::{Deserialize, Serialize};
 use std::fs;
 use std::path::PathBuf;
@@ -59,6 +59,41 @@ impl DownloadProgress {
         Ok(())
     }
 
+    /// Gets ready to download the song at `url`, picking up where a previous run left off
+    /// unless `force_restart` is set or that run was for a different song.
+    pub fn start(&self, url: &str, force_restart: bool) -> Result<()> {
+        if force_restart {
+            tracing::info!("Force restart requested, clearing previous progress");
+            self.clear()?;
+        } else if self.is_same_url(url)? {
+            let completed = self.get_completed_tracks()?;
+            if !completed.is_empty() {
+                tracing::info!(
+                    "Resuming previous download. Already completed {} tracks:",
+                    completed.len()
+                );
+                for track in &completed {
+                    tracing::info!("  ✓ {}", track);
+                }
+            }
+        } else if !self.get_completed_tracks()?.is_empty() {
+            // Different URL, clear the old progress
+            tracing::info!("Different song detected, clearing previous progress");
+            self.clear()?;
+        }
+
+        self.set_url(url)
+    }
+
+    /// The tracks [`DownloadProgress::start`] would count as already downloaded, without
+    /// changing anything.
+    pub fn resumable_tracks(&self, url: &str, force_restart: bool) -> Result<Vec<String>> {
+        if force_restart || !self.is_same_url(url)? {
+            return Ok(Vec::new());
+        }
+        self.get_completed_tracks()
+    }
+
     pub fn is_same_url(&self, url: &str) -> Result<bool> {
         if !self.progress_file.exists() {
             return Ok(false);
@@ -0,0 +1,240 @@
+use crate::error::Result;
+use headless_chrome::protocol::cdp::types::Event;
+use headless_chrome::protocol::cdp::Browser::{
+    DownloadProgressEventStateOption, SetDownloadBehavior, SetDownloadBehaviorBehaviorOption,
+};
+use headless_chrome::Tab;
+use std::collections::HashMap;
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DownloadState {
+    InProgress { received: f64, total: f64 },
+    Completed,
+    Canceled,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Download {
+    /// Chrome's id for the download, which every progress event refers to
+    pub guid: String,
+    pub url: String,
+    pub filename: String,
+    pub state: DownloadState,
+}
+
+impl Download {
+    /// Whether this is the download of a track, going by the modal's download `link` when it
+    /// could be read (the url, or the file name it ends in), otherwise by the track's name
+    /// appearing in the file name.
+    pub fn is_for(&self, link: Option<&url::Url>, track: &str) -> bool {
+        match link {
+            Some(link) => {
+                let filename = link
+                    .path_segments()
+                    .and_then(|mut segments| segments.next_back())
+                    .map(|name| urlencoding::decode(name).map(|name| name.to_string()));
+                self.url == link.as_str()
+                    || matches!(filename, Some(Ok(name)) if name == self.filename)
+            }
+            None => {
+                let simplify = |s: &str| {
+                    s.chars()
+                        .filter(|c| c.is_alphanumeric())
+                        .flat_map(char::to_lowercase)
+                        .collect::<String>()
+                };
+                let track = simplify(track);
+                !track.is_empty() && simplify(&self.filename).contains(&track)
+            }
+        }
+    }
+}
+
+/// Every download the browser has told us about, in the order they began.
+#[derive(Debug, Default)]
+pub struct DownloadTracker {
+    downloads: Vec<Download>,
+    index: HashMap<String, usize>,
+}
+
+impl DownloadTracker {
+    pub fn begin(&mut self, guid: &str, url: &str, filename: &str) {
+        self.index.insert(guid.to_string(), self.downloads.len());
+        self.downloads.push(Download {
+            guid: guid.to_string(),
+            url: url.to_string(),
+            filename: filename.to_string(),
+            state: DownloadState::InProgress {
+                received: 0.0,
+                total: 0.0,
+            },
+        });
+    }
+
+    pub fn update(&mut self, guid: &str, state: DownloadState) {
+        if let Some(&index) = self.index.get(guid) {
+            self.downloads[index].state = state;
+        }
+    }
+
+    /// How many downloads have begun so far
+    pub fn started(&self) -> usize {
+        self.downloads.len()
+    }
+
+    /// The `n`th download to begin, counting from zero
+    pub fn nth(&self, n: usize) -> Option<&Download> {
+        self.downloads.get(n)
+    }
+
+    pub fn get(&self, guid: &str) -> Option<&Download> {
+        self.index.get(guid).map(|&index| &self.downloads[index])
+    }
+
+    /// The first download from the `n`th on (counting from zero) that is for the track, passing
+    /// over any others, such as a late one from an attempt that timed out.
+    pub fn find_from(&self, n: usize, link: Option<&url::Url>, track: &str) -> Option<&Download> {
+        self.downloads
+            .iter()
+            .skip(n)
+            .find(|download| download.is_for(link, track))
+    }
+}
+
+/// Follows the downloads started from a tab through the DevTools download events, so we know
+/// exactly when each file starts, progresses and finishes.
+pub struct DownloadWatcher {
+    state: Arc<(Mutex<DownloadTracker>, Condvar)>,
+}
+
+impl DownloadWatcher {
+    /// Enables download events for `tab`'s browser context, saving files to `download_path`, or
+    /// to the browser's default location if `None`. `browser_context_id` is the context `tab` was
+    /// opened in, or `None` for the default one.
+    pub fn watch(
+        tab: &Arc<Tab>,
+        browser_context_id: Option<&str>,
+        download_path: Option<&str>,
+    ) -> Result<Self> {
+        let state = Arc::new((Mutex::new(DownloadTracker::default()), Condvar::new()));
+
+        let listener_state = state.clone();
+        tab.add_event_listener(Arc::new(move |event: &Event| {
+            let (tracker, changed) = &*listener_state;
+            match event {
+                Event::BrowserDownloadWillBegin(e) => {
+                    let params = &e.params;
+                    tracker.lock().unwrap().begin(
+                        &params.guid,
+                        &params.url,
+                        &params.suggested_filename,
+                    );
+                }
+                Event::BrowserDownloadProgress(e) => {
+                    let params = &e.params;
+                    let state = match params.state {
+                        DownloadProgressEventStateOption::InProgress => DownloadState::InProgress {
+                            received: params.received_bytes,
+                            total: params.total_bytes,
+                        },
+                        DownloadProgressEventStateOption::Completed => DownloadState::Completed,
+                        DownloadProgressEventStateOption::Canceled => DownloadState::Canceled,
+                    };
+                    tracker.lock().unwrap().update(&params.guid, state);
+                }
+                _ => return,
+            }
+            changed.notify_all();
+        }))?;
+
+        let behavior = match download_path {
+            Some(_) => SetDownloadBehaviorBehaviorOption::Allow,
+            None => SetDownloadBehaviorBehaviorOption::Default,
+        };
+        tracing::debug!("call_method (set download behavior)");
+        tab.call_method(SetDownloadBehavior {
+            browser_context_id: browser_context_id.map(str::to_string),
+            behavior,
+            download_path: download_path.map(str::to_string),
+            events_enabled: Some(true),
+        })?;
+
+        Ok(DownloadWatcher { state })
+    }
+
+    /// Stops the browser saving anything for `tab`'s browser context, for when we fetch the
+    /// files ourselves.
+    pub fn deny(tab: &Tab, browser_context_id: Option<&str>) -> Result<()> {
+        tracing::debug!("call_method (deny downloads)");
+        tab.call_method(SetDownloadBehavior {
+            browser_context_id: browser_context_id.map(str::to_string),
+            behavior: SetDownloadBehaviorBehaviorOption::Deny,
+            download_path: None,
+            events_enabled: None,
+        })?;
+        Ok(())
+    }
+
+    /// How many downloads have begun so far. Pass this to [`DownloadWatcher::wait_for_start`]
+    /// before doing whatever starts the next one.
+    pub fn started(&self) -> usize {
+        self.state.0.lock().unwrap().started()
+    }
+
+    /// The latest state of the download with `guid`.
+    pub fn get(&self, guid: &str) -> Option<Download> {
+        self.state.0.lock().unwrap().get(guid).cloned()
+    }
+
+    /// Waits for a download of the track to begin, from download number `n` (counting from zero)
+    /// on. See [`Download::is_for`].
+    pub fn wait_for_start(
+        &self,
+        n: usize,
+        link: Option<&url::Url>,
+        track: &str,
+        timeout: Duration,
+    ) -> Option<Download> {
+        self.wait_until(timeout, |tracker| {
+            tracker.find_from(n, link, track).cloned()
+        })
+    }
+
+    /// Waits for the download to complete or be cancelled, returning its final state, or `None`
+    /// if it is still going after `timeout`.
+    pub fn wait_for_finish(&self, guid: &str, timeout: Duration) -> Option<Download> {
+        let mut last_logged = Instant::now();
+        self.wait_until(timeout, |tracker| {
+            let download = tracker.get(guid)?;
+            match download.state {
+                DownloadState::InProgress { received, total } => {
+                    if last_logged.elapsed() >= Duration::from_secs(5) {
+                        tracing::debug!("{}: {} of {} bytes", download.filename, received, total);
+                        last_logged = Instant::now();
+                    }
+                    None
+                }
+                _ => Some(download.clone()),
+            }
+        })
+    }
+
+    fn wait_until<T>(
+        &self,
+        timeout: Duration,
+        mut check: impl FnMut(&DownloadTracker) -> Option<T>,
+    ) -> Option<T> {
+        let (tracker, changed) = &*self.state;
+        let deadline = Instant::now() + timeout;
+        let mut tracker = tracker.lock().unwrap();
+        loop {
+            if let Some(result) = check(&tracker) {
+                return Some(result);
+            }
+            let remaining = deadline.checked_duration_since(Instant::now())?;
+            tracker = changed.wait_timeout(tracker, remaining).unwrap().0;
+        }
+    }
+}
@@ -1,11 +1,76 @@
+use crate::diagnostics;
 use crate::download_progress::DownloadProgress;
-use headless_chrome::{Browser, LaunchOptions, Tab};
-use std::error::Error;
+use crate::error::{KvError, Result};
+use crate::events::Event;
+use crate::har::HarRecorder;
+use crate::observer::{LogObserver, Observer};
+use crate::rate_limit::{RateLimiter, RateLimits};
+use crate::retry::RetryPolicy;
+use crate::screencast::Screencast;
+use crate::selectors::Selectors;
+use crate::wait;
+use headless_chrome::{Browser, Element, LaunchOptions, Tab};
+use serde::Deserialize;
+use std::ffi::OsStr;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// How often [`Driver::wait_until`] checks its condition
+pub const POLL_INTERVAL: Duration = Duration::from_millis(100);
 
 pub struct Config {
     pub domain: String,
     pub headless: bool,
     pub download_path: Option<String>,
+    /// DevTools websocket url of an already running browser to attach to instead of launching one
+    pub connect: Option<String>,
+    pub browser: BrowserOptions,
+    pub selectors: Selectors,
+    /// Where to save a screenshot and snapshot of the page when something fails. Disabled if `None`.
+    pub diagnostics_dir: Option<PathBuf>,
+    /// Record a screencast of every tab into this directory
+    pub record_dir: Option<PathBuf>,
+    /// Record the network traffic of every tab into this HAR file
+    pub har_path: Option<PathBuf>,
+    /// Hand a "verify you are human" check over to the user instead of failing
+    pub interactive: bool,
+    /// Restore the session from the system keychain before signing in, and save it there after
+    pub persist_session: bool,
+    pub retry: RetryPolicy,
+    pub limits: RateLimits,
+    /// Where the rate limiter keeps its usage between runs. In memory only if `None`.
+    pub usage_file: Option<PathBuf>,
+    /// Told about each step of the run, e.g. to show it on screen. Logs it by default.
+    pub observers: Vec<Arc<dyn Observer>>,
+}
+
+/// How the browser is launched. Ignored when attaching to a running browser.
+#[derive(Clone)]
+pub struct BrowserOptions {
+    /// The Chrome/Chromium binary to run. Downloaded automatically if not set.
+    pub executable: Option<PathBuf>,
+    /// A persistent profile directory, instead of a fresh temporary one for each run
+    pub user_data_dir: Option<PathBuf>,
+    /// HTTP or SOCKS proxy, i.e. `http://proxy:3128` or `socks5://127.0.0.1:1080`
+    pub proxy: Option<String>,
+    /// Disabling the sandbox is required when running as root (i.e. in Docker)
+    pub sandbox: bool,
+    pub args: Vec<String>,
+    pub window_size: (u32, u32),
+}
+
+impl Default for BrowserOptions {
+    fn default() -> Self {
+        BrowserOptions {
+            executable: None,
+            user_data_dir: None,
+            proxy: None,
+            sandbox: true,
+            args: vec![],
+            window_size: (1440, 1200),
+        }
+    }
 }
 
 impl Default for Config {
@@ -14,6 +79,18 @@ impl Default for Config {
             domain: "www.karaoke-version.com".to_string(),
             headless: false,
             download_path: None,
+            connect: None,
+            browser: BrowserOptions::default(),
+            selectors: Selectors::default(),
+            diagnostics_dir: None,
+            record_dir: None,
+            har_path: None,
+            interactive: false,
+            persist_session: true,
+            retry: RetryPolicy::default(),
+            limits: RateLimits::default(),
+            usage_file: None,
+            observers: vec![Arc::new(LogObserver)],
         }
     }
 }
@@ -22,54 +99,224 @@ pub struct Driver {
     pub config: Config,
     pub browser: Browser,
     pub progress: DownloadProgress,
+    screencast: Option<Screencast>,
+    har: Option<HarRecorder>,
+    pub(crate) rate_limiter: RateLimiter,
 }
 
 impl Driver {
-    pub fn new(config: Config) -> Self {
-        let browser = Browser::new(LaunchOptions {
-            headless: config.headless,
-            window_size: Some((1440, 1200)),
-            enable_logging: true,
-            ..Default::default()
-        })
-        .expect("Unable to create headless chromium browser");
-
-        if let Some(download_path) = &config.download_path {
-            tracing::info!("Setting download path to: {}", download_path);
-            Driver::set_download_path(&browser, download_path)
-                .expect("failed to set download path");
-        }
+    pub fn new(config: Config) -> Result<Self> {
+        let browser = match &config.connect {
+            Some(ws_url) => {
+                tracing::info!("Connecting to browser at {}", ws_url);
+                Browser::connect(ws_url.clone()).map_err(KvError::Launch)?
+            }
+            None => Driver::launch(&config.browser, config.headless).map_err(KvError::Launch)?,
+        };
 
         let download_path = config.download_path.clone();
 
-        Driver {
+        let screencast = match &config.record_dir {
+            Some(dir) => Some(Screencast::new(dir)?),
+            None => None,
+        };
+        let har = config.har_path.as_deref().map(HarRecorder::new);
+        let rate_limiter = RateLimiter::new(config.limits.clone(), config.usage_file.clone())?;
+
+        Ok(Driver {
             config,
             browser,
             progress: DownloadProgress::new_with_path(download_path.as_deref()),
+            screencast,
+            har,
+            rate_limiter,
+        })
+    }
+
+    /// Opens a tab, with recording attached if it was asked for.
+    pub fn new_tab(&self) -> Result<Arc<Tab>> {
+        let tab = self.browser.new_tab()?;
+        self.attach_recorders(&tab, "recording")?;
+        Ok(tab)
+    }
+
+    /// Starts recording `tab` if recording was asked for, with its screencast in
+    /// `<recording>.gif`.
+    pub(crate) fn attach_recorders(&self, tab: &Arc<Tab>, recording: &str) -> Result<()> {
+        if let Some(screencast) = &self.screencast {
+            screencast.record(tab, recording)?;
+        }
+        if let Some(har) = &self.har {
+            har.record(tab)?;
+        }
+        Ok(())
+    }
+
+    /// Writes out anything recorded during the run. Call this once the run is over, whether
+    /// or not it succeeded.
+    pub fn finish(&self) -> Result<()> {
+        if let Some(screencast) = &self.screencast {
+            tracing::info!("Assembling screencast...");
+            for path in screencast.finish()? {
+                tracing::info!("Screencast saved to {}", path.display());
+            }
+        }
+        if let Some(har) = &self.har {
+            let path = har.finish()?;
+            tracing::info!("Network trace saved to {}", path.display());
         }
+        Ok(())
     }
 
-    fn set_download_path(browser: &Browser, download_path: &str) -> Result<(), Box<dyn Error>> {
-        let tab = browser
-            .new_tab()
-            .expect("couldn't open a new tab to set download behavior");
+    pub(crate) fn launch(options: &BrowserOptions, headless: bool) -> anyhow::Result<Browser> {
+        let mut args: Vec<String> = options.args.clone();
+        if let Some(proxy) = &options.proxy {
+            args.push(format!("--proxy-server={}", proxy));
+        }
 
-        let download_behavior_method = headless_chrome::protocol::cdp::Browser::SetDownloadBehavior {
-            browser_context_id: None,
-            behavior: headless_chrome::protocol::cdp::Browser::SetDownloadBehaviorBehaviorOption::Allow,
-            download_path: Some(download_path.to_string()),
-            events_enabled: None
+        Browser::new(LaunchOptions {
+            headless,
+            sandbox: options.sandbox,
+            window_size: Some(options.window_size),
+            path: options.executable.clone(),
+            user_data_dir: options.user_data_dir.clone(),
+            args: args.iter().map(OsStr::new).collect(),
+            enable_logging: true,
+            ..Default::default()
+        })
+    }
+
+    /// Captures the state of `tab` for an error and attaches the location to it.
+    pub fn with_diagnostics(&self, tab: &Tab, label: &str, error: KvError) -> KvError {
+        let Some(dir) = &self.config.diagnostics_dir else {
+            return error;
         };
-        tracing::debug!("call_method (set download behavior)");
-        tab.call_method(download_behavior_method)?;
+        if matches!(error, KvError::Diagnosed { .. }) {
+            return error;
+        }
 
-        Ok(())
+        match diagnostics::capture(tab, dir, label, &error) {
+            Ok(diagnostics) => KvError::Diagnosed {
+                error: Box::new(error),
+                diagnostics,
+            },
+            Err(e) => {
+                tracing::warn!("Unable to save diagnostics: {}", e);
+                error
+            }
+        }
+    }
+
+    /// Waits for `condition` to hold, checking it every [`POLL_INTERVAL`] for at most
+    /// `timeout`. `waiting_for` describes the condition in the error if it never does.
+    pub fn wait_until(
+        &self,
+        waiting_for: &str,
+        timeout: Duration,
+        condition: impl FnMut() -> Result<bool>,
+    ) -> Result<()> {
+        if wait::poll(timeout, POLL_INTERVAL, condition)? {
+            Ok(())
+        } else {
+            Err(KvError::Timeout {
+                waiting_for: waiting_for.to_string(),
+                timeout,
+            })
+        }
+    }
+
+    /// Tells every observer about `event`.
+    pub(crate) fn emit(&self, event: Event) {
+        for observer in &self.config.observers {
+            observer.on_event(&event);
+        }
     }
 
-    pub fn type_fast(&self, tab: &Tab, text: &str) {
+    pub fn type_fast(&self, tab: &Tab, text: &str) -> Result<()> {
         for c in text.chars() {
-            tab.send_character(&c.to_string())
-                .expect("failed to send character");
+            tab.send_character(&c.to_string())?;
         }
+        Ok(())
+    }
+}
+
+/// Tab helpers that report failures as [`KvError`]s naming what went wrong.
+pub trait TabExt {
+    fn navigate(&self, url: &str) -> Result<()>;
+    fn find(&self, selector: &str) -> Result<Element<'_>>;
+    fn find_all(&self, selector: &str) -> Result<Vec<Element<'_>>>;
+    fn wait_for(&self, selector: &str, timeout: Duration) -> Result<Element<'_>>;
+    /// Whether an element matching `selector` is on the page and shown.
+    fn is_visible(&self, selector: &str) -> Result<bool>;
+}
+
+impl TabExt for Tab {
+    fn navigate(&self, url: &str) -> Result<()> {
+        self.navigate_to(url)
+            .and_then(|tab| tab.wait_until_navigated())
+            .map_err(|source| KvError::Navigation {
+                url: url.to_string(),
+                source,
+            })?;
+        Ok(())
+    }
+
+    fn find(&self, selector: &str) -> Result<Element<'_>> {
+        self.find_element(selector)
+            .map_err(|_| KvError::SelectorMissing(selector.to_string()))
+    }
+
+    fn find_all(&self, selector: &str) -> Result<Vec<Element<'_>>> {
+        self.find_elements(selector)
+            .map_err(|_| KvError::SelectorMissing(selector.to_string()))
+    }
+
+    fn wait_for(&self, selector: &str, timeout: Duration) -> Result<Element<'_>> {
+        self.wait_for_element_with_custom_timeout(selector, timeout)
+            .map_err(|_| KvError::SelectorMissing(selector.to_string()))
+    }
+
+    fn is_visible(&self, selector: &str) -> Result<bool> {
+        let script = format!(
+            "(function() {{ const el = document.querySelector({}); return !!el && ({}).call(el); }})()",
+            serde_json::to_string(selector)?,
+            IS_VISIBLE_JS
+        );
+        Ok(self
+            .evaluate(&script, false)?
+            .value
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false))
     }
 }
+
+/// Whether `element` is shown and not disabled, so clicking it will do something.
+pub fn is_clickable(element: &Element) -> Result<bool> {
+    let script = format!(
+        "function() {{ return ({}).call(this) && !this.disabled && !this.classList.contains('disabled') && this.getAttribute('aria-disabled') !== 'true'; }}",
+        IS_VISIBLE_JS
+    );
+    Ok(element
+        .call_js_fn(&script, vec![], false)?
+        .value
+        .and_then(|v| v.as_bool())
+        .unwrap_or(false))
+}
+
+const IS_VISIBLE_JS: &str = "function() { const style = window.getComputedStyle(this); const rect = this.getBoundingClientRect(); return rect.width > 0 && rect.height > 0 && style.visibility !== 'hidden'; }";
+
+#[derive(Deserialize)]
+struct BrowserVersion {
+    #[serde(rename = "webSocketDebuggerUrl")]
+    web_socket_debugger_url: String,
+}
+
+/// Looks up the browser's DevTools websocket url from a Chrome started with `--remote-debugging-port`.
+pub fn websocket_url_for_port(port: u16) -> Result<String> {
+    let url = format!("http://127.0.0.1:{}/json/version", port);
+    let version: BrowserVersion = ureq::get(&url)
+        .call()
+        .map_err(|e| KvError::Launch(e.into()))?
+        .into_json()?;
+    Ok(version.web_socket_debugger_url)
+}
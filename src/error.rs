@@ -0,0 +1,163 @@
+use crate::tasks::download_song::DownloadError;
+use std::fmt::Display;
+use std::path::PathBuf;
+use std::time::Duration;
+
+pub type Result<T, E = KvError> = std::result::Result<T, E>;
+
+#[derive(Debug)]
+pub enum KvError {
+    /// The browser could not be launched or attached to
+    Launch(anyhow::Error),
+    /// A page failed to load
+    Navigation {
+        url: String,
+        source: anyhow::Error,
+    },
+    /// An element we rely on is not on the page, usually because the site changed
+    SelectorMissing(String),
+    /// Signing in failed or there are no credentials to sign in with
+    Auth(String),
+    Download(DownloadError),
+    /// The page never got to the state we were waiting for
+    Timeout {
+        waiting_for: String,
+        timeout: Duration,
+    },
+    /// Any other failure talking to the browser
+    Browser(anyhow::Error),
+    Keystore(keyring::Error),
+    Io(std::io::Error),
+    Json(serde_json::Error),
+    /// Any of the above, with a screenshot and page snapshot saved for debugging
+    Diagnosed {
+        error: Box<KvError>,
+        diagnostics: PathBuf,
+    },
+}
+
+impl KvError {
+    /// The underlying error, looking through any diagnostics attached to it.
+    pub fn kind(&self) -> &KvError {
+        match self {
+            Self::Diagnosed { error, .. } => error.kind(),
+            _ => self,
+        }
+    }
+
+    /// The process exit code for this error, so scripts can tell failures apart.
+    pub fn exit_code(&self) -> u8 {
+        match self.kind() {
+            Self::Launch(_) => 3,
+            Self::Auth(_) => 4,
+            Self::Navigation { .. } => 5,
+            Self::SelectorMissing(_) => 6,
+            Self::Download(DownloadError::NotPurchased) => 8,
+            Self::Download(DownloadError::HumanVerificationRequired) => 9,
+            Self::Download(_) => 7,
+            _ => 1,
+        }
+    }
+
+    /// Whether trying again could help. Timeouts and page or network hiccups are worth another
+    /// go; a song that isn't purchased or a bot check will fail the same way every time.
+    pub fn is_retryable(&self) -> bool {
+        match self.kind() {
+            Self::Navigation { .. }
+            | Self::SelectorMissing(_)
+            | Self::Timeout { .. }
+            | Self::Browser(_)
+            | Self::Io(_) => true,
+            Self::Download(e) => matches!(
+                e,
+                DownloadError::ModalTimeout(_)
+                    | DownloadError::StartTimeout(_)
+                    | DownloadError::CompletionTimeout(_)
+                    | DownloadError::Canceled(_)
+                    | DownloadError::InvalidDownloadLink(_)
+                    | DownloadError::Http(_)
+                    | DownloadError::SizeMismatch { .. }
+                    | DownloadError::SoloNotSet { .. }
+            ),
+            _ => false,
+        }
+    }
+}
+
+impl Display for KvError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Launch(e) => write!(f, "Unable to start the browser: {}", e),
+            Self::Navigation { url, source } => write!(f, "Failed to load {}: {}", url, source),
+            Self::SelectorMissing(selector) => write!(
+                f,
+                "Couldn't find '{}' on the page. The site may have changed.",
+                selector
+            ),
+            Self::Auth(msg) => f.write_str(msg),
+            Self::Download(e) => e.fmt(f),
+            Self::Timeout {
+                waiting_for,
+                timeout,
+            } => write!(
+                f,
+                "Timed out after {:?} waiting for {}",
+                timeout, waiting_for
+            ),
+            Self::Browser(e) => write!(f, "Browser error: {}", e),
+            Self::Keystore(e) => write!(f, "Keychain error: {}", e),
+            Self::Io(e) => e.fmt(f),
+            Self::Json(e) => e.fmt(f),
+            Self::Diagnosed { error, diagnostics } => write!(
+                f,
+                "{} (screenshot and page saved to {})",
+                error,
+                diagnostics.display()
+            ),
+        }
+    }
+}
+
+impl std::error::Error for KvError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Download(e) => Some(e),
+            Self::Keystore(e) => Some(e),
+            Self::Io(e) => Some(e),
+            Self::Json(e) => Some(e),
+            Self::Diagnosed { error, .. } => error.source(),
+            _ => None,
+        }
+    }
+}
+
+impl From<DownloadError> for KvError {
+    fn from(e: DownloadError) -> Self {
+        Self::Download(e)
+    }
+}
+
+// headless_chrome reports everything as anyhow errors
+impl From<anyhow::Error> for KvError {
+    fn from(e: anyhow::Error) -> Self {
+        Self::Browser(e)
+    }
+}
+
+impl From<keyring::Error> for KvError {
+    fn from(e: keyring::Error) -> Self {
+        Self::Keystore(e)
+    }
+}
+
+impl From<std::io::Error> for KvError {
+    fn from(e: std::io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for KvError {
+    fn from(e: serde_json::Error) -> Self {
+        Self::Json(e)
+    }
+}
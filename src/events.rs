@@ -0,0 +1,139 @@
+use crate::observer::Observer;
+use crate::tasks::download_song::DownloadReport;
+use serde::Serialize;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+/// A step in the life of a run, as reported to each [`Observer`]. Serialized as
+/// `{"event": "track_completed", ...}`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event {
+    SignedIn {
+        user: String,
+    },
+    SongLoaded {
+        song: String,
+        title: Option<String>,
+        tracks: Vec<String>,
+        /// Tracks a previous run already downloaded, which are skipped
+        already_downloaded: Vec<String>,
+    },
+    PitchChanged {
+        song: String,
+        from: i8,
+        to: i8,
+    },
+    /// Generating the track waits until `until` (RFC 3339) to stay within the rate limits
+    RateLimited {
+        song: String,
+        track: String,
+        until: String,
+        reason: String,
+    },
+    TrackStarted {
+        song: String,
+        track: String,
+        attempt: u32,
+    },
+    TrackSoloed {
+        song: String,
+        track: String,
+        attempt: u32,
+    },
+    /// The site has finished generating the track and offers it for download
+    ModalAppeared {
+        song: String,
+        track: String,
+        attempt: u32,
+    },
+    TrackProgress {
+        song: String,
+        track: String,
+        received: u64,
+        total: Option<u64>,
+    },
+    /// The file has been transferred and is being checked
+    TrackVerifying {
+        song: String,
+        track: String,
+    },
+    TrackCompleted {
+        song: String,
+        track: String,
+        /// Where the file was saved, or `None` when only its link was collected
+        path: Option<PathBuf>,
+    },
+    TrackFailed {
+        song: String,
+        track: String,
+        attempt: u32,
+        error: String,
+        /// Whether another attempt follows
+        retrying: bool,
+    },
+    RetryScheduled {
+        song: String,
+        track: String,
+        /// The attempt about to be made
+        attempt: u32,
+        wait_secs: f64,
+    },
+    SongCompleted {
+        song: String,
+        /// Tracks saved (or whose link was collected) by this run
+        completed: usize,
+        /// Tracks an earlier run had already saved
+        skipped: usize,
+        failed: Vec<String>,
+    },
+    /// The song couldn't be downloaded at all, i.e. it isn't purchased
+    SongFailed {
+        song: String,
+        error: String,
+    },
+}
+
+impl Event {
+    /// The [`Event::SongCompleted`] summing up `report`.
+    pub fn song_completed(report: &DownloadReport) -> Self {
+        Event::SongCompleted {
+            song: report.song.clone(),
+            completed: report.downloaded.len() + report.links.len(),
+            skipped: report.skipped.len(),
+            failed: report.failed.clone(),
+        }
+    }
+}
+
+/// Writes [`Event`]s as newline-delimited JSON, for scripts following along with
+/// `--output json`.
+pub struct EventStream {
+    out: Mutex<Box<dyn Write + Send>>,
+}
+
+impl EventStream {
+    pub fn new(out: Box<dyn Write + Send>) -> Self {
+        EventStream {
+            out: Mutex::new(out),
+        }
+    }
+
+    pub fn stdout() -> Self {
+        Self::new(Box::new(io::stdout()))
+    }
+}
+
+impl Observer for EventStream {
+    fn on_event(&self, event: &Event) {
+        let mut out = self.out.lock().unwrap();
+        let written = serde_json::to_writer(&mut *out, event)
+            .map_err(io::Error::from)
+            .and_then(|_| out.write_all(b"\n"))
+            .and_then(|_| out.flush());
+        if let Err(e) = written {
+            tracing::warn!("Unable to write event: {}", e);
+        }
+    }
+}
@@ -0,0 +1,314 @@
+use crate::error::Result;
+use headless_chrome::protocol::cdp::types::Event;
+use headless_chrome::protocol::cdp::Network;
+use headless_chrome::Tab;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+const REDACTED: &str = "[redacted]";
+
+/// Headers whose values are never written to the HAR
+const SENSITIVE_HEADERS: [&str; 4] = [
+    "cookie",
+    "set-cookie",
+    "authorization",
+    "proxy-authorization",
+];
+
+/// Form fields whose values are never written to the HAR, matched as substrings of the name
+const SENSITIVE_FIELDS: [&str; 4] = ["pass", "token", "secret", "auth"];
+
+/// One request/response pair, as seen over the DevTools protocol.
+#[derive(Debug, Clone, Default)]
+pub struct HarEntry {
+    /// Wall clock time the request was sent, in seconds since the unix epoch
+    pub started: f64,
+    /// Monotonic timestamps (in seconds) the timings below are measured with
+    pub start_timestamp: f64,
+    pub response_timestamp: Option<f64>,
+    pub end_timestamp: Option<f64>,
+
+    pub method: String,
+    pub url: String,
+    pub request_headers: Vec<(String, String)>,
+    pub post_data: Option<String>,
+
+    pub status: Option<u32>,
+    pub status_text: String,
+    pub http_version: String,
+    pub response_headers: Vec<(String, String)>,
+    pub mime_type: String,
+    pub body_size: f64,
+    pub redirect_url: String,
+    /// Set if the request failed or was cancelled
+    pub error: Option<String>,
+}
+
+/// Records the network traffic of every tab it is attached to and writes it out as a HAR file,
+/// with cookies, auth headers and passwords redacted.
+pub struct HarRecorder {
+    path: PathBuf,
+    state: Arc<Mutex<RecorderState>>,
+}
+
+#[derive(Default)]
+struct RecorderState {
+    entries: Vec<HarEntry>,
+    /// `(tab, request id)` -> index into `entries` of the request still in flight
+    pending: HashMap<(usize, String), usize>,
+    tabs: usize,
+}
+
+impl HarRecorder {
+    pub fn new(path: &Path) -> Self {
+        HarRecorder {
+            path: path.to_path_buf(),
+            state: Arc::new(Mutex::new(RecorderState::default())),
+        }
+    }
+
+    pub fn record(&self, tab: &Arc<Tab>) -> Result<()> {
+        let tab_id = {
+            let mut state = self.state.lock().unwrap();
+            state.tabs += 1;
+            state.tabs
+        };
+
+        let state = self.state.clone();
+        tab.add_event_listener(Arc::new(move |event: &Event| {
+            state.lock().unwrap().handle(tab_id, event);
+        }))?;
+
+        tab.call_method(Network::Enable {
+            max_total_buffer_size: None,
+            max_resource_buffer_size: None,
+            max_post_data_size: None,
+        })?;
+
+        Ok(())
+    }
+
+    /// Writes everything recorded so far to the HAR file and returns its path.
+    pub fn finish(&self) -> Result<PathBuf> {
+        let entries = self.state.lock().unwrap().entries.clone();
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&self.path, serde_json::to_string_pretty(&to_har(&entries))?)?;
+        Ok(self.path.clone())
+    }
+}
+
+impl RecorderState {
+    fn handle(&mut self, tab_id: usize, event: &Event) {
+        match event {
+            Event::NetworkRequestWillBeSent(e) => {
+                let params = &e.params;
+                let key = (tab_id, params.request_id.clone());
+
+                // a redirect reuses the request id, so the previous hop ends here
+                if let Some(redirect) = &params.redirect_response {
+                    if let Some(index) = self.pending.remove(&key) {
+                        let entry = &mut self.entries[index];
+                        set_response(entry, redirect, params.timestamp);
+                        entry.redirect_url = params.request.url.clone();
+                        entry.end_timestamp = Some(params.timestamp);
+                    }
+                }
+
+                let request = &params.request;
+                let url = match &request.url_fragment {
+                    Some(fragment) => format!("{}{}", request.url, fragment),
+                    None => request.url.clone(),
+                };
+                self.entries.push(HarEntry {
+                    started: params.wall_time,
+                    start_timestamp: params.timestamp,
+                    method: request.method.clone(),
+                    url,
+                    request_headers: headers(&request.headers),
+                    post_data: request.post_data.clone(),
+                    ..Default::default()
+                });
+                self.pending.insert(key, self.entries.len() - 1);
+            }
+            Event::NetworkResponseReceived(e) => {
+                let params = &e.params;
+                if let Some(&index) = self.pending.get(&(tab_id, params.request_id.clone())) {
+                    set_response(&mut self.entries[index], &params.response, params.timestamp);
+                }
+            }
+            Event::NetworkLoadingFinished(e) => {
+                let params = &e.params;
+                if let Some(index) = self.pending.remove(&(tab_id, params.request_id.clone())) {
+                    let entry = &mut self.entries[index];
+                    entry.body_size = params.encoded_data_length;
+                    entry.end_timestamp = Some(params.timestamp);
+                }
+            }
+            Event::NetworkLoadingFailed(e) => {
+                let params = &e.params;
+                if let Some(index) = self.pending.remove(&(tab_id, params.request_id.clone())) {
+                    let entry = &mut self.entries[index];
+                    entry.error = Some(params.error_text.clone());
+                    entry.end_timestamp = Some(params.timestamp);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn set_response(entry: &mut HarEntry, response: &Network::Response, timestamp: f64) {
+    entry.status = Some(response.status);
+    entry.status_text = response.status_text.clone();
+    entry.http_version = response.protocol.clone().unwrap_or_default();
+    entry.response_headers = headers(&response.headers);
+    entry.mime_type = response.mime_type.clone();
+    entry.response_timestamp = Some(timestamp);
+}
+
+fn headers(headers: &Network::Headers) -> Vec<(String, String)> {
+    let Some(Value::Object(map)) = &headers.0 else {
+        return vec![];
+    };
+    // chrome joins repeated headers with newlines
+    map.iter()
+        .flat_map(|(name, value)| {
+            value
+                .as_str()
+                .unwrap_or_default()
+                .split('\n')
+                .map(|v| (name.clone(), v.to_string()))
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+/// Replaces the value of cookie and auth headers.
+pub fn redact_header(name: &str, value: &str) -> String {
+    if SENSITIVE_HEADERS.contains(&name.to_ascii_lowercase().as_str()) {
+        REDACTED.to_string()
+    } else {
+        value.to_string()
+    }
+}
+
+/// Replaces the values of password-like fields in a form-encoded body. Bodies that aren't
+/// form-encoded are left alone unless they mention such a field, in which case the whole body
+/// is dropped.
+pub fn redact_post_data(body: &str) -> String {
+    let is_sensitive = |name: &str| {
+        let name = name.to_ascii_lowercase();
+        SENSITIVE_FIELDS.iter().any(|field| name.contains(field))
+    };
+
+    let is_form = body
+        .split('&')
+        .all(|pair| pair.contains('=') && !pair.contains(char::is_whitespace));
+    if !is_form {
+        let lowercase = body.to_ascii_lowercase();
+        if SENSITIVE_FIELDS
+            .iter()
+            .any(|field| lowercase.contains(field))
+        {
+            return REDACTED.to_string();
+        }
+        return body.to_string();
+    }
+
+    body.split('&')
+        .map(|pair| match pair.split_once('=') {
+            Some((name, _)) if is_sensitive(name) => format!("{}={}", name, REDACTED),
+            _ => pair.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join("&")
+}
+
+/// Builds a HAR 1.2 document from the recorded entries, redacting as it goes.
+pub fn to_har(entries: &[HarEntry]) -> Value {
+    let entries: Vec<Value> = entries.iter().map(to_har_entry).collect();
+    json!({
+        "log": {
+            "version": "1.2",
+            "creator": {
+                "name": env!("CARGO_PKG_NAME"),
+                "version": env!("CARGO_PKG_VERSION"),
+            },
+            "pages": [],
+            "entries": entries,
+        }
+    })
+}
+
+fn to_har_entry(entry: &HarEntry) -> Value {
+    let to_ms = |seconds: f64| (seconds * 1000.0).max(0.0);
+    let end = entry.end_timestamp.unwrap_or(entry.start_timestamp);
+    let response_at = entry.response_timestamp.unwrap_or(end);
+    let wait = to_ms(response_at - entry.start_timestamp);
+    let receive = to_ms(end - response_at);
+
+    let header_list = |headers: &[(String, String)]| -> Vec<Value> {
+        headers
+            .iter()
+            .map(|(name, value)| json!({ "name": name, "value": redact_header(name, value) }))
+            .collect()
+    };
+
+    let query_string: Vec<Value> = url::Url::parse(&entry.url)
+        .map(|url| {
+            url.query_pairs()
+                .map(|(name, value)| json!({ "name": name, "value": value }))
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let mut request = json!({
+        "method": entry.method,
+        "url": entry.url,
+        "httpVersion": entry.http_version,
+        "cookies": [],
+        "headers": header_list(&entry.request_headers),
+        "queryString": query_string,
+        "headersSize": -1,
+        "bodySize": entry.post_data.as_ref().map_or(0, |data| data.len() as i64),
+    });
+    if let Some(data) = &entry.post_data {
+        let mime_type = entry
+            .request_headers
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case("content-type"))
+            .map(|(_, value)| value.as_str())
+            .unwrap_or_default();
+        request["postData"] = json!({ "mimeType": mime_type, "text": redact_post_data(data) });
+    }
+
+    let mut har_entry = json!({
+        "startedDateTime": chrono::DateTime::from_timestamp_millis((entry.started * 1000.0) as i64)
+            .unwrap_or_default()
+            .to_rfc3339_opts(chrono::SecondsFormat::Millis, true),
+        "time": wait + receive,
+        "request": request,
+        "response": {
+            "status": entry.status.unwrap_or(0),
+            "statusText": entry.status_text,
+            "httpVersion": entry.http_version,
+            "cookies": [],
+            "headers": header_list(&entry.response_headers),
+            "content": { "size": entry.body_size as i64, "mimeType": entry.mime_type },
+            "redirectURL": entry.redirect_url,
+            "headersSize": -1,
+            "bodySize": entry.body_size as i64,
+        },
+        "cache": {},
+        "timings": { "send": 0, "wait": wait, "receive": receive },
+    });
+    if let Some(error) = &entry.error {
+        har_entry["_error"] = json!(error);
+    }
+    har_entry
+}
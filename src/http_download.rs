@@ -0,0 +1,202 @@
+use crate::error::{KvError, Result};
+use crate::tasks::download_song::DownloadError;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// How many times an interrupted transfer is picked up again before giving up
+const MAX_RESUMES: u32 = 5;
+
+pub struct Request {
+    pub url: String,
+    /// Sent with every request, i.e. the browser's cookies and user agent
+    pub headers: Vec<(String, String)>,
+}
+
+/// Why a single attempt stopped
+enum Failure {
+    /// The connection dropped; what was received so far is kept and the rest requested again
+    Interrupted(String),
+    Fatal(KvError),
+}
+
+impl<E: Into<KvError>> From<E> for Failure {
+    fn from(e: E) -> Self {
+        Failure::Fatal(e.into())
+    }
+}
+
+/// Where a download is written to until it is complete
+pub fn part_path(dest: &Path) -> PathBuf {
+    let mut name = dest.file_name().unwrap_or_default().to_os_string();
+    name.push(".part");
+    dest.with_file_name(name)
+}
+
+/// What a `.part` file was downloaded from, kept next to it so it is only resumed with more of
+/// the same file
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Partial {
+    pub url: String,
+    /// The response's `ETag`, or else its `Last-Modified`, sent back as `If-Range`
+    pub validator: Option<String>,
+}
+
+impl Partial {
+    /// Where the details of the `.part` file for `dest` are kept
+    pub fn path(dest: &Path) -> PathBuf {
+        let mut name = dest.file_name().unwrap_or_default().to_os_string();
+        name.push(".part.json");
+        dest.with_file_name(name)
+    }
+
+    pub fn load(dest: &Path) -> Option<Self> {
+        let contents = fs::read_to_string(Self::path(dest)).ok()?;
+        serde_json::from_str(&contents).ok()
+    }
+
+    pub fn save(&self, dest: &Path) -> Result<()> {
+        fs::write(Self::path(dest), serde_json::to_string(self)?)?;
+        Ok(())
+    }
+
+    fn remove(dest: &Path) {
+        let _ = fs::remove_file(Self::path(dest));
+    }
+}
+
+/// Streams `request` to `dest`, calling `on_progress` with the bytes received so far and the
+/// total size (if the server told us). A partial `.part` file left over from an earlier attempt
+/// at the same url is resumed with a `Range` request, and only if the file hasn't changed since
+/// (`If-Range`); any other `.part` file is started over. The finished file is checked against
+/// the size the server announced before being moved into place. Returns the size of the file.
+pub fn fetch(
+    request: &Request,
+    dest: &Path,
+    mut on_progress: impl FnMut(u64, Option<u64>),
+) -> Result<u64> {
+    let part = part_path(dest);
+    let agent = ureq::AgentBuilder::new()
+        .timeout_connect(Duration::from_secs(30))
+        .timeout_read(Duration::from_secs(60))
+        .build();
+
+    let mut resumes = 0;
+    loop {
+        match fetch_once(&agent, request, dest, &mut on_progress) {
+            Ok(size) => {
+                fs::rename(&part, dest)?;
+                Partial::remove(dest);
+                return Ok(size);
+            }
+            Err(Failure::Interrupted(reason)) if resumes < MAX_RESUMES => {
+                resumes += 1;
+                tracing::warn!("Download interrupted ({}), resuming...", reason);
+            }
+            Err(Failure::Interrupted(reason)) => {
+                return Err(DownloadError::Http(format!("interrupted: {}", reason)).into())
+            }
+            Err(Failure::Fatal(e)) => return Err(e),
+        }
+    }
+}
+
+fn fetch_once(
+    agent: &ureq::Agent,
+    request: &Request,
+    dest: &Path,
+    on_progress: &mut impl FnMut(u64, Option<u64>),
+) -> std::result::Result<u64, Failure> {
+    let part = &part_path(dest);
+    let partial = Partial::load(dest).filter(|partial| partial.url == request.url);
+    let offset = match &partial {
+        Some(_) => fs::metadata(part).map(|m| m.len()).unwrap_or(0),
+        None => 0,
+    };
+
+    let mut http_request = agent.get(&request.url);
+    for (name, value) in &request.headers {
+        http_request = http_request.set(name, value);
+    }
+    if offset > 0 {
+        tracing::debug!("Resuming {} from byte {}", part.display(), offset);
+        http_request = http_request.set("Range", &format!("bytes={}-", offset));
+        if let Some(validator) = partial.as_ref().and_then(|p| p.validator.as_deref()) {
+            http_request = http_request.set("If-Range", validator);
+        }
+    }
+
+    let response = match http_request.call() {
+        Ok(response) => response,
+        Err(ureq::Error::Status(416, _)) if offset > 0 => {
+            // the partial file doesn't fit what the server has now, so start over
+            fs::remove_file(part)?;
+            Partial::remove(dest);
+            return Err(Failure::Interrupted(
+                "partial file is out of date".to_string(),
+            ));
+        }
+        Err(ureq::Error::Status(code, response)) => {
+            return Err(DownloadError::Http(format!("{} {}", code, response.status_text())).into())
+        }
+        Err(ureq::Error::Transport(e)) => return Err(Failure::Interrupted(e.to_string())),
+    };
+
+    // a server that ignores the range sends the whole file again
+    let resumed = offset > 0 && response.status() == 206;
+    let start = if resumed { offset } else { 0 };
+    let content_length = response
+        .header("Content-Length")
+        .and_then(|v| v.parse::<u64>().ok());
+    let total = match response.header("Content-Range") {
+        Some(range) if resumed => range.rsplit('/').next().and_then(|v| v.parse::<u64>().ok()),
+        _ => content_length.map(|length| start + length),
+    };
+
+    // otherwise the server sent the whole file, i.e. because it changed since the partial file
+    let mut file = if resumed {
+        OpenOptions::new().append(true).open(part)?
+    } else {
+        let file = File::create(part)?;
+        Partial {
+            url: request.url.clone(),
+            validator: response
+                .header("ETag")
+                .or_else(|| response.header("Last-Modified"))
+                .map(str::to_string),
+        }
+        .save(dest)?;
+        file
+    };
+
+    let mut reader = response.into_reader();
+    let mut buffer = vec![0; 64 * 1024];
+    let mut received = start;
+    on_progress(received, total);
+    loop {
+        let read = match reader.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(e) => return Err(Failure::Interrupted(e.to_string())),
+        };
+        file.write_all(&buffer[..read])?;
+        received += read as u64;
+        on_progress(received, total);
+    }
+    file.flush()?;
+
+    match total {
+        Some(total) if received < total => Err(Failure::Interrupted(format!(
+            "received {} of {} bytes",
+            received, total
+        ))),
+        Some(total) if received > total => Err(DownloadError::SizeMismatch {
+            expected: total,
+            received,
+        }
+        .into()),
+        _ => Ok(received),
+    }
+}
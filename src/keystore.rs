@@ -1,5 +1,5 @@
-use anyhow::Result;
-use headless_chrome::protocol::cdp::Network::{Cookie, CookieParam};
+use crate::error::Result;
+use crate::session::Session;
 use keyring::Entry;
 use serde::{Deserialize, Serialize};
 
@@ -7,7 +7,7 @@ pub struct Keystore {}
 
 const KEYSTORE_SERVICE: &str = "kv-downloader";
 const KV_CREDENTIALS_KEY: &str = "KV_CREDENTIALS";
-const KV_SESSION_COOKIE_KEY: &str = "KV_SESSION";
+const KV_SESSION_KEY: &str = "KV_SESSION";
 
 #[derive(Debug, PartialEq, Serialize, Deserialize)]
 pub struct Credentials {
@@ -30,7 +30,7 @@ impl Keystore {
         if let Ok(entry) = Entry::new(KEYSTORE_SERVICE, KV_CREDENTIALS_KEY) {
             let _ = entry.delete_credential().ok();
         }
-        Ok(())
+        Keystore::clear_session()
     }
 
     pub fn get_credentials() -> Result<Credentials> {
@@ -40,34 +40,22 @@ impl Keystore {
         Ok(creds)
     }
 
-    pub fn get_auth_cookie() -> Result<CookieParam> {
-        let secret = Entry::new(KEYSTORE_SERVICE, KV_SESSION_COOKIE_KEY)?.get_secret()?;
-        let cookie: Cookie = serde_json::from_slice(&secret).expect("Unable to deserialize cookie");
-
-        // return a cookie param so it can be set on the tab type (get/set use differnet types)
-        let cookie_param = CookieParam {
-            name: cookie.name,
-            value: cookie.value,
-            url: None,
-            domain: Some(cookie.domain),
-            secure: Some(cookie.secure),
-            http_only: Some(cookie.http_only),
-            same_site: cookie.same_site,
-            path: Some(cookie.path),
-            expires: Some(cookie.expires),
-            priority: Some(cookie.priority),
-            same_party: Some(cookie.same_party),
-            source_scheme: Some(cookie.source_scheme),
-            source_port: Some(cookie.source_port),
-            partition_key: cookie.partition_key,
-        };
+    pub fn get_session() -> Result<Session> {
+        let secret = Entry::new(KEYSTORE_SERVICE, KV_SESSION_KEY)?.get_secret()?;
+        let session: Session = serde_json::from_slice(&secret)?;
+        Ok(session)
+    }
 
-        Ok(cookie_param)
+    pub fn set_session(session: &Session) -> Result<()> {
+        let value = serde_json::to_vec(session)?;
+        Entry::new(KEYSTORE_SERVICE, KV_SESSION_KEY)?.set_secret(&value)?;
+        Ok(())
     }
 
-    pub fn set_auth_cookie(cookie: &Cookie) -> Result<()> {
-        let value = serde_json::to_vec_pretty(&cookie).expect("Unable to serialize cookie");
-        Entry::new(KEYSTORE_SERVICE, KV_SESSION_COOKIE_KEY)?.set_secret(&value)?;
+    pub fn clear_session() -> Result<()> {
+        if let Ok(entry) = Entry::new(KEYSTORE_SERVICE, KV_SESSION_KEY) {
+            let _ = entry.delete_credential().ok();
+        }
         Ok(())
     }
 }
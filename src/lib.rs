@@ -1,6 +1,27 @@
+pub mod client;
 pub mod commands;
+pub mod config_file;
+pub mod cookies_txt;
+pub mod diagnostics;
 pub mod download_progress;
+pub mod downloads;
 pub mod driver;
+pub mod error;
+pub mod events;
+pub mod har;
+pub mod http_download;
 pub mod keystore;
+pub mod links;
+pub mod mixer;
+pub mod observer;
+pub mod progress_view;
 pub mod prompt;
+pub mod rate_limit;
+pub mod retry;
+pub mod screencast;
+pub mod selectors;
+pub mod session;
 pub mod tasks;
+pub mod wait;
+
+pub use client::{KvClient, KvClientBuilder};
@@ -0,0 +1,60 @@
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+/// Where a track can be downloaded from, for handing the transfer to another tool
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TrackLink {
+    pub track: String,
+    pub filename: String,
+    pub url: String,
+    /// Headers the site expects with the request, including the session cookies
+    pub headers: BTreeMap<String, String>,
+}
+
+#[derive(Serialize)]
+struct LinksFile<'a> {
+    song: &'a str,
+    tracks: &'a [TrackLink],
+}
+
+/// `{ "song": ..., "tracks": [{ "track", "filename", "url", "headers" }] }`
+pub fn to_json(song_url: &str, links: &[TrackLink]) -> serde_json::Result<String> {
+    serde_json::to_string_pretty(&LinksFile {
+        song: song_url,
+        tracks: links,
+    })
+}
+
+/// The links of one song of a batch
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SongLinks {
+    pub song: String,
+    /// The song's own directory, which is where aria2 is told to save its tracks
+    #[serde(skip)]
+    pub dir: PathBuf,
+    pub tracks: Vec<TrackLink>,
+}
+
+/// `{ "songs": [{ "song", "tracks" }] }`, each song laid out as in [`to_json`]
+pub fn songs_to_json(songs: &[SongLinks]) -> serde_json::Result<String> {
+    serde_json::to_string_pretty(&serde_json::json!({ "songs": songs }))
+}
+
+/// An input file for `aria2c -i`, saving each track under its own name in `dir` (or aria2's
+/// working directory if `None`).
+pub fn to_aria2(links: &[TrackLink], dir: Option<&str>) -> String {
+    let mut out = String::new();
+    for link in links {
+        out.push_str(&link.url);
+        out.push('\n');
+        out.push_str(&format!("  out={}\n", link.filename));
+        if let Some(dir) = dir {
+            out.push_str(&format!("  dir={}\n", dir));
+        }
+        for (name, value) in &link.headers {
+            out.push_str(&format!("  header={}: {}\n", name, value));
+        }
+    }
+    out
+}
@@ -1,13 +1,13 @@
 use anyhow::Result;
-use clap::{command, Parser, Subcommand};
+use clap::{Parser, Subcommand};
 use dotenv::dotenv;
-
-mod commands;
-mod download_progress;
-mod driver;
-mod keystore;
-mod prompt;
-mod tasks;
+use kv_downloader::{
+    commands, config_file::ConfigFile, error::KvError, progress_view::ProgressView,
+    selectors::Selectors,
+};
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::sync::Arc;
 
 #[derive(Debug, Parser)]
 #[command(name = "kv-downloader")]
@@ -18,6 +18,22 @@ struct Cli {
 
     #[arg(global = true, long, help = "enable debug logging")]
     debug: bool,
+
+    #[arg(
+        global = true,
+        long,
+        value_name = "PATH",
+        help = "config file to use (default: ~/.config/kv-downloader/config.toml)"
+    )]
+    config: Option<PathBuf>,
+
+    #[arg(
+        global = true,
+        long,
+        value_name = "PATH",
+        help = "TOML file overriding the page selectors (default: ~/.config/kv-downloader/selectors.toml)"
+    )]
+    selectors: Option<PathBuf>,
 }
 
 #[derive(Debug, Subcommand)]
@@ -25,23 +41,63 @@ enum Commands {
     Auth,
     Logout,
     #[command(arg_required_else_help = true)]
-    Download(commands::DownloadArgs),
+    Download(Box<commands::DownloadArgs>),
+    /// Check that the song page still has everything this tool relies on
+    #[command(arg_required_else_help = true)]
+    Doctor(commands::doctor::DoctorArgs),
+    /// Move the saved session to and from other tools
+    Session {
+        #[command(subcommand)]
+        command: commands::session::SessionCommand,
+    },
 }
 
-fn main() -> Result<()> {
+fn main() -> ExitCode {
     dotenv().ok();
     let cli = Cli::parse();
-    tracing_subscriber::fmt()
-        .with_max_level(if cli.debug {
-            tracing::Level::DEBUG
-        } else {
-            tracing::Level::INFO
-        })
-        .init();
+    match run(cli) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {:?}", e);
+            let code = e.downcast_ref::<KvError>().map_or(1, KvError::exit_code);
+            ExitCode::from(code)
+        }
+    }
+}
+
+fn run(cli: Cli) -> Result<()> {
+    // downloads get a live view of every track on a terminal, which takes over from the info
+    // logs, unless stdout is for the json events or a dry run's plan
+    let json_output = matches!(&cli.command, Commands::Download(args) if args.json_output());
+    let progress_view = match &cli.command {
+        Commands::Download(args) if !json_output && !args.dry_run() => {
+            ProgressView::for_stdout(args.song_count()).map(Arc::new)
+        }
+        _ => None,
+    };
+    let subscriber = tracing_subscriber::fmt().with_max_level(if cli.debug {
+        tracing::Level::DEBUG
+    } else if progress_view.is_some() {
+        tracing::Level::WARN
+    } else {
+        tracing::Level::INFO
+    });
+    match &progress_view {
+        Some(view) => subscriber.with_writer(view.log_writer()).init(),
+        None if json_output => subscriber.with_writer(std::io::stderr).init(),
+        None => subscriber.init(),
+    }
+
+    let config_file = ConfigFile::load(cli.config.as_deref())?;
+    let selectors = Selectors::load(cli.selectors.as_deref())?;
     match cli.command {
         Commands::Auth => commands::auth::run()?,
         Commands::Logout => commands::logout::run()?,
-        Commands::Download(args) => commands::Download::run(args)?,
+        Commands::Download(args) => {
+            commands::Download::run(*args, &config_file, selectors, progress_view)?
+        }
+        Commands::Doctor(args) => commands::doctor::run(args, &config_file, selectors)?,
+        Commands::Session { command } => commands::session::run(command)?,
     }
 
     Ok(())
@@ -0,0 +1,82 @@
+use crate::driver::Driver;
+use crate::error::{KvError, Result};
+use crate::selectors::Selectors;
+use headless_chrome::Tab;
+use serde::Deserialize;
+
+/// The solo and mute buttons of every track in the mixer, in the order of the tracks
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct MixerState {
+    pub soloed: Vec<bool>,
+    pub muted: Vec<bool>,
+}
+
+impl MixerState {
+    /// The tracks that can be heard, and so end up in the generated file: the soloed ones if
+    /// any are, otherwise every track, in both cases leaving out the muted ones.
+    pub fn audible(&self) -> Vec<usize> {
+        let any_soloed = self.soloed.iter().any(|&soloed| soloed);
+        (0..self.soloed.len())
+            .filter(|&index| !any_soloed || self.soloed[index])
+            .filter(|&index| !self.muted.get(index).copied().unwrap_or(false))
+            .collect()
+    }
+
+    /// Whether the track at `index` is the only one that can be heard.
+    pub fn is_solo(&self, index: usize) -> bool {
+        self.audible() == [index]
+    }
+
+    /// Whether no track is soloed or muted, as after pressing reset.
+    pub fn is_reset(&self) -> bool {
+        !self.soloed.iter().chain(&self.muted).any(|&on| on)
+    }
+
+    /// A line listing each track that is soloed or muted, for the logs.
+    pub fn describe(&self, track_names: &[String]) -> String {
+        let name = |index: usize| {
+            track_names
+                .get(index)
+                .cloned()
+                .unwrap_or_else(|| format!("track {}", index + 1))
+        };
+        let list = |flags: &[bool]| {
+            let names: Vec<_> = (0..flags.len())
+                .filter(|&index| flags[index])
+                .map(name)
+                .collect();
+            match names.is_empty() {
+                true => "none".to_string(),
+                false => names.join(", "),
+            }
+        };
+        format!(
+            "soloed: {}; muted: {}",
+            list(&self.soloed),
+            list(&self.muted)
+        )
+    }
+}
+
+impl Driver {
+    /// Reads which tracks are soloed and muted from the mixer on `tab`.
+    pub fn mixer_state(&self, tab: &Tab) -> Result<MixerState> {
+        let script = mixer_state_js(&self.config.selectors)?;
+        let json = tab
+            .evaluate(&script, false)?
+            .value
+            .and_then(|v| v.as_str().map(str::to_string))
+            .ok_or_else(|| KvError::Browser(anyhow::anyhow!("Unable to read the mixer")))?;
+        Ok(serde_json::from_str(&json)?)
+    }
+}
+
+fn mixer_state_js(sel: &Selectors) -> Result<String> {
+    Ok(format!(
+        "(function() {{ const states = (all, on) => Array.from(document.querySelectorAll(all)).map(el => el.matches(on)); return JSON.stringify({{ soloed: states({}, {}), muted: states({}, {}) }}); }})()",
+        serde_json::to_string(&sel.solo_button)?,
+        serde_json::to_string(&sel.soloed_button)?,
+        serde_json::to_string(&sel.mute_button)?,
+        serde_json::to_string(&sel.muted_button)?,
+    ))
+}
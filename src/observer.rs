@@ -0,0 +1,88 @@
+use crate::events::Event;
+
+/// Gets told about every step of a run: signing in, loading the song, each track's progress,
+/// retries and failures. The logs, the progress view and the JSON output are all observers,
+/// and a library user can add their own with [`crate::KvClientBuilder::observer`].
+pub trait Observer: Send + Sync {
+    fn on_event(&self, event: &Event);
+}
+
+/// Logs each step, which is what the command line shows without the progress view.
+pub struct LogObserver;
+
+impl Observer for LogObserver {
+    fn on_event(&self, event: &Event) {
+        match event {
+            Event::SignedIn { user } => tracing::info!(user = user, "Signed in"),
+            Event::SongLoaded {
+                title,
+                tracks,
+                already_downloaded,
+                ..
+            } => {
+                tracing::info!(
+                    "Loaded '{}' with {} tracks",
+                    title.as_deref().unwrap_or_default(),
+                    tracks.len()
+                );
+                for track in already_downloaded {
+                    tracing::info!("Skipping track '{}' (already downloaded)", track);
+                }
+            }
+            Event::PitchChanged { from, to, .. } => {
+                tracing::info!("Pitch set to {} (was {})", to, from)
+            }
+            Event::TrackStarted { track, attempt, .. } => match attempt {
+                1 => tracing::info!("Processing track '{}'", track),
+                _ => tracing::info!("Attempt {} for track '{}'", attempt, track),
+            },
+            Event::RateLimited { until, reason, .. } => tracing::warn!(
+                "Pausing until {} ({}). Leave this running or stop it and run the same command later.",
+                clock_time(until),
+                reason
+            ),
+            Event::TrackSoloed { track, .. } => tracing::debug!("- soloed '{}'", track),
+            Event::ModalAppeared { .. } => tracing::debug!("- download modal appeared"),
+            Event::TrackProgress { .. } | Event::TrackVerifying { .. } => {}
+            Event::TrackCompleted { track, path, .. } => {
+                tracing::info!("- '{}' complete!", track);
+                if let Some(path) = path {
+                    tracing::debug!("- saved to {}", path.display());
+                }
+            }
+            Event::TrackFailed {
+                track,
+                attempt,
+                error,
+                retrying: true,
+                ..
+            } => tracing::warn!("Attempt {} failed for '{}': {}", attempt, track, error),
+            Event::TrackFailed {
+                track,
+                attempt,
+                error,
+                retrying: false,
+                ..
+            } => tracing::error!(
+                "Failed to download '{}' after {} attempts: {}",
+                track,
+                attempt,
+                error
+            ),
+            Event::RetryScheduled {
+                track, wait_secs, ..
+            } => tracing::info!("Waiting {:.1}s before retrying '{}'...", wait_secs, track),
+            Event::SongCompleted { song, .. } => tracing::debug!("Finished {}", song),
+            Event::SongFailed { song, error } => {
+                tracing::debug!("Failed to download {}: {}", song, error)
+            }
+        }
+    }
+}
+
+/// The `HH:MM` of an RFC 3339 timestamp, or the whole timestamp if it doesn't parse.
+pub(crate) fn clock_time(timestamp: &str) -> String {
+    chrono::DateTime::parse_from_rfc3339(timestamp)
+        .map(|time| time.format("%H:%M").to_string())
+        .unwrap_or_else(|_| timestamp.to_string())
+}
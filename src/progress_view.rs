@@ -0,0 +1,274 @@
+use crate::events::Event;
+use crate::observer::{clock_time, Observer};
+use crate::tasks::batch::song_slug;
+use indicatif::{MultiProgress, ProgressBar, ProgressDrawTarget, ProgressStyle};
+use std::collections::HashMap;
+use std::io::{self, IsTerminal, Write};
+use std::sync::Mutex;
+use std::time::Duration;
+use tracing_subscriber::fmt::MakeWriter;
+
+/// Where a track is up to
+#[derive(Debug, Clone, PartialEq)]
+pub enum TrackState {
+    Queued,
+    /// The site is mixing the track down
+    Generating,
+    Downloading {
+        received: u64,
+        total: Option<u64>,
+    },
+    Verifying,
+    /// Waiting to stay within the rate limits
+    Paused {
+        until: String,
+        reason: String,
+    },
+    Done,
+    Failed(String),
+}
+
+/// A live terminal view of the run: one line per track showing where it is up to, under an
+/// overall bar with an ETA.
+pub struct ProgressView {
+    multi: MultiProgress,
+    overall: ProgressBar,
+    tracks: Mutex<HashMap<(String, String), TrackLine>>,
+    /// Whether tracks of several songs are shown, and so need the song in front of their name
+    several_songs: bool,
+}
+
+impl ProgressView {
+    /// A view drawn on stdout, or `None` when stdout isn't a terminal and plain logs work better.
+    pub fn for_stdout(songs: usize) -> Option<Self> {
+        io::stdout()
+            .is_terminal()
+            .then(|| Self::new(ProgressDrawTarget::stdout(), songs))
+    }
+
+    pub fn new(target: ProgressDrawTarget, songs: usize) -> Self {
+        let multi = MultiProgress::with_draw_target(target);
+        let overall = multi.add(ProgressBar::new(0));
+        overall.set_style(
+            ProgressStyle::with_template("{prefix:.bold} [{bar:30}] {pos}/{len} tracks, ETA {eta}")
+                .unwrap()
+                .progress_chars("=> "),
+        );
+        overall.set_prefix("Overall");
+        ProgressView {
+            multi,
+            overall,
+            tracks: Mutex::new(HashMap::new()),
+            several_songs: songs > 1,
+        }
+    }
+
+    /// Shows `track` of the song at `song_url` as being in `state`, on its `attempt`th attempt.
+    pub fn track(&self, song_url: &str, track: &str, attempt: u32, state: TrackState) {
+        let mut tracks = self.tracks.lock().unwrap();
+        let line = tracks
+            .entry((song_url.to_string(), track.to_string()))
+            .or_insert_with(|| {
+                self.overall.inc_length(1);
+                let bar = self.multi.add(ProgressBar::new(0));
+                bar.set_prefix(if self.several_songs {
+                    format!("{} / {}", song_slug(song_url), track)
+                } else {
+                    track.to_string()
+                });
+                TrackLine {
+                    bar,
+                    attempt,
+                    state: TrackState::Queued,
+                }
+            });
+        line.attempt = attempt;
+        line.state = state.clone();
+        let bar = &line.bar;
+
+        let retry = match attempt {
+            0 | 1 => String::new(),
+            attempt => format!(" (retry {})", attempt - 1),
+        };
+        match state {
+            TrackState::Queued => self.status(bar, format!("queued{}", retry)),
+            TrackState::Generating => {
+                self.status(bar, format!("generating{}", retry));
+                bar.enable_steady_tick(Duration::from_millis(120));
+            }
+            TrackState::Downloading { received, total } => {
+                let template = match total {
+                    Some(_) => {
+                        "{spinner} {prefix:30!} [{bar:30}] {bytes}/{total_bytes} ({percent}%){msg}"
+                    }
+                    None => "{spinner} {prefix:30!} {bytes}{msg}",
+                };
+                bar.set_style(
+                    ProgressStyle::with_template(template)
+                        .unwrap()
+                        .progress_chars("=> "),
+                );
+                bar.set_length(total.unwrap_or(0));
+                bar.set_position(received);
+                bar.set_message(retry);
+            }
+            TrackState::Verifying => self.status(bar, format!("verifying{}", retry)),
+            TrackState::Paused { until, reason } => {
+                self.status(bar, format!("paused until {} ({}){}", until, reason, retry))
+            }
+            TrackState::Done => {
+                bar.set_style(Self::status_style());
+                bar.finish_with_message(format!("done{}", retry));
+                self.overall.inc(1);
+            }
+            TrackState::Failed(error) => {
+                bar.set_style(Self::status_style());
+                bar.abandon_with_message(format!("failed{}: {}", retry, error));
+                self.overall.inc(1);
+            }
+        }
+    }
+
+    /// Where `track` of the song at `song_url` is up to and the message shown next to it, if it
+    /// has been shown at all.
+    pub fn track_status(&self, song_url: &str, track: &str) -> Option<(TrackState, String)> {
+        self.tracks
+            .lock()
+            .unwrap()
+            .get(&(song_url.to_string(), track.to_string()))
+            .map(|line| (line.state.clone(), line.bar.message()))
+    }
+
+    /// How many of the tracks shown are finished (done or failed), and how many there are.
+    pub fn overall(&self) -> (u64, u64) {
+        (self.overall.position(), self.overall.length().unwrap_or(0))
+    }
+
+    /// Leaves the final state of every track on screen.
+    pub fn finish(&self) {
+        self.overall.finish();
+    }
+
+    /// Where log lines should go while the view is shown, so they're printed above it rather
+    /// than through it.
+    pub fn log_writer(&self) -> LogWriter {
+        LogWriter(self.multi.clone())
+    }
+
+    /// The attempt `track` of the song at `song_url` was last shown on.
+    fn attempt(&self, song_url: &str, track: &str) -> u32 {
+        self.tracks
+            .lock()
+            .unwrap()
+            .get(&(song_url.to_string(), track.to_string()))
+            .map_or(1, |line| line.attempt)
+    }
+
+    fn status(&self, bar: &ProgressBar, message: String) {
+        bar.set_style(Self::status_style());
+        bar.set_message(message);
+    }
+
+    fn status_style() -> ProgressStyle {
+        ProgressStyle::with_template("{spinner} {prefix:30!} {msg}").unwrap()
+    }
+}
+
+impl Observer for ProgressView {
+    fn on_event(&self, event: &Event) {
+        match event {
+            Event::SongLoaded {
+                song,
+                tracks,
+                already_downloaded,
+                ..
+            } => {
+                for track in tracks.iter().filter(|t| !already_downloaded.contains(t)) {
+                    self.track(song, track, 1, TrackState::Queued);
+                }
+            }
+            Event::TrackStarted {
+                song,
+                track,
+                attempt,
+            } => self.track(song, track, *attempt, TrackState::Generating),
+            Event::RateLimited {
+                song,
+                track,
+                until,
+                reason,
+            } => {
+                let state = TrackState::Paused {
+                    until: clock_time(until),
+                    reason: reason.clone(),
+                };
+                self.track(song, track, self.attempt(song, track), state);
+            }
+            Event::TrackProgress {
+                song,
+                track,
+                received,
+                total,
+            } => {
+                let state = TrackState::Downloading {
+                    received: *received,
+                    total: *total,
+                };
+                self.track(song, track, self.attempt(song, track), state);
+            }
+            Event::TrackVerifying { song, track } => self.track(
+                song,
+                track,
+                self.attempt(song, track),
+                TrackState::Verifying,
+            ),
+            Event::TrackCompleted { song, track, .. } => {
+                self.track(song, track, self.attempt(song, track), TrackState::Done)
+            }
+            Event::TrackFailed {
+                song,
+                track,
+                attempt,
+                retrying: true,
+                ..
+            } => self.track(song, track, attempt + 1, TrackState::Queued),
+            Event::TrackFailed {
+                song,
+                track,
+                attempt,
+                error,
+                retrying: false,
+            } => self.track(song, track, *attempt, TrackState::Failed(error.clone())),
+            _ => {}
+        }
+    }
+}
+
+struct TrackLine {
+    bar: ProgressBar,
+    /// The attempt last shown, for the updates that don't say
+    attempt: u32,
+    state: TrackState,
+}
+
+/// Writes log lines to stdout with the progress view cleared out of the way.
+#[derive(Clone)]
+pub struct LogWriter(MultiProgress);
+
+impl Write for LogWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.suspend(|| io::stdout().write(buf))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        io::stdout().flush()
+    }
+}
+
+impl<'a> MakeWriter<'a> for LogWriter {
+    type Writer = LogWriter;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        self.clone()
+    }
+}
@@ -0,0 +1,141 @@
+use crate::error::Result;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::num::NonZeroU32;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::thread::sleep;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const HOUR_MS: u64 = 60 * 60 * 1000;
+const DAY_MS: u64 = 24 * HOUR_MS;
+
+/// How hard we allow ourselves to use the site. Each generated track counts once, retries
+/// included.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RateLimits {
+    /// Minimum time between two track generations
+    pub min_delay: Duration,
+    pub per_hour: Option<NonZeroU32>,
+    pub per_day: Option<NonZeroU32>,
+}
+
+/// When tracks were generated over the last day, kept across runs so the caps hold for batches
+/// split over several invocations.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Usage {
+    /// Milliseconds since the unix epoch, oldest first
+    pub generations: Vec<u64>,
+}
+
+impl Usage {
+    /// How long to wait before the next generation is allowed at `now`, and why.
+    pub fn wait_time(&self, limits: &RateLimits, now: u64) -> Option<(Duration, String)> {
+        let mut waits = vec![];
+
+        if let Some(&last) = self.generations.last() {
+            let ready_at = last + limits.min_delay.as_millis() as u64;
+            if ready_at > now {
+                waits.push((
+                    ready_at - now,
+                    format!("waiting {:?} between tracks", limits.min_delay),
+                ));
+            }
+        }
+
+        for (cap, window, name) in [
+            (limits.per_hour, HOUR_MS, "hourly"),
+            (limits.per_day, DAY_MS, "daily"),
+        ] {
+            let Some(cap) = cap.map(|cap| cap.get() as usize) else {
+                continue;
+            };
+            let recent: Vec<u64> = self
+                .generations
+                .iter()
+                .copied()
+                .filter(|&at| at + window > now)
+                .collect();
+            if recent.len() >= cap {
+                // wait until enough of them have dropped out of the window
+                let freed_at = recent[recent.len() - cap] + window;
+                waits.push((
+                    freed_at.saturating_sub(now),
+                    format!("{} limit of {} tracks reached", name, cap),
+                ));
+            }
+        }
+
+        waits
+            .into_iter()
+            .max_by_key(|(wait, _)| *wait)
+            .map(|(wait, reason)| (Duration::from_millis(wait), reason))
+    }
+
+    /// Forgets generations that no longer count towards any limit.
+    pub fn prune(&mut self, now: u64) {
+        self.generations.retain(|&at| at + DAY_MS > now);
+    }
+}
+
+/// Paces track generations according to [`RateLimits`], pausing rather than going over them.
+pub struct RateLimiter {
+    limits: RateLimits,
+    /// Where the usage is kept between runs. In memory only if `None`.
+    usage_file: Option<PathBuf>,
+    usage: Mutex<Usage>,
+}
+
+impl RateLimiter {
+    pub fn new(limits: RateLimits, usage_file: Option<PathBuf>) -> Result<Self> {
+        let usage = match &usage_file {
+            Some(path) if path.exists() => serde_json::from_str(&fs::read_to_string(path)?)?,
+            _ => Usage::default(),
+        };
+        Ok(RateLimiter {
+            limits,
+            usage_file,
+            usage: Mutex::new(usage),
+        })
+    }
+
+    /// Blocks until another track may be generated, then counts it. Pauses of a minute or more
+    /// are announced through `on_pause`, with when they end and why.
+    pub fn acquire(
+        &self,
+        mut on_pause: impl FnMut(chrono::DateTime<chrono::Local>, &str),
+    ) -> Result<()> {
+        let mut usage = self.usage.lock().unwrap();
+        while let Some((wait, reason)) = usage.wait_time(&self.limits, now()) {
+            if wait >= Duration::from_secs(60) {
+                on_pause(chrono::Local::now() + wait, &reason);
+            } else {
+                tracing::debug!("Pausing for {:?} ({})", wait, reason);
+            }
+            sleep(wait);
+        }
+
+        let now = now();
+        usage.prune(now);
+        usage.generations.push(now);
+        if let Some(path) = &self.usage_file {
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::write(path, serde_json::to_string(&*usage)?)?;
+        }
+        Ok(())
+    }
+
+    /// `~/.local/share/kv-downloader/usage.json` on Linux, and the platform equivalent elsewhere.
+    pub fn default_usage_file() -> Option<PathBuf> {
+        dirs::data_local_dir().map(|dir| dir.join("kv-downloader").join("usage.json"))
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or_default()
+}
@@ -0,0 +1,55 @@
+use std::time::Duration;
+
+/// How failed tracks are retried, and how long each step may take.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetryPolicy {
+    /// Attempts per track, including the first
+    pub max_attempts: u32,
+    /// Wait after the first failure, doubling after each one after that
+    pub backoff: Duration,
+    pub max_backoff: Duration,
+    /// Up to this fraction of each wait is added at random, so retries don't line up
+    pub jitter: f64,
+    /// How long to wait for the download modal on the first attempt
+    pub modal_timeout: Duration,
+    /// Added to the modal timeout on each retry, since a slow site tends to stay slow
+    pub modal_timeout_step: Duration,
+    /// How long to wait for the browser to start the download once the modal is shown
+    pub download_start_timeout: Duration,
+    /// How long a single file may take to download
+    pub download_timeout: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 3,
+            backoff: Duration::from_secs(5),
+            max_backoff: Duration::from_secs(120),
+            jitter: 0.25,
+            modal_timeout: Duration::from_secs(60),
+            modal_timeout_step: Duration::from_secs(30),
+            download_start_timeout: Duration::from_secs(30),
+            download_timeout: Duration::from_secs(300),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// The wait after failed attempt number `attempt` (counting from 1), before jitter.
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempt.saturating_sub(1));
+        self.backoff.saturating_mul(factor).min(self.max_backoff)
+    }
+
+    /// [`RetryPolicy::backoff`] with jitter added, where `random` is in `0.0..1.0`.
+    pub fn backoff_with_jitter(&self, attempt: u32, random: f64) -> Duration {
+        let backoff = self.backoff(attempt);
+        backoff + backoff.mul_f64(self.jitter.clamp(0.0, 1.0) * random.clamp(0.0, 1.0))
+    }
+
+    /// How long to wait for the download modal on attempt number `attempt` (counting from 1).
+    pub fn modal_timeout(&self, attempt: u32) -> Duration {
+        self.modal_timeout + self.modal_timeout_step * attempt.saturating_sub(1)
+    }
+}
@@ -0,0 +1,166 @@
+use crate::error::Result;
+use base64::prelude::BASE64_STANDARD;
+use base64::Engine;
+use headless_chrome::protocol::cdp::types::Event;
+use headless_chrome::protocol::cdp::Page::StartScreencastFormatOption;
+use headless_chrome::Tab;
+use image::codecs::gif::{GifEncoder, Repeat};
+use image::{Delay, Frame, ImageFormat};
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Longest a single frame is shown in the assembled recording, so idle waits don't drag on
+const MAX_FRAME_DELAY: Duration = Duration::from_secs(2);
+
+struct CapturedFrame {
+    /// the recording the frame belongs to
+    name: String,
+    path: PathBuf,
+    /// seconds since the unix epoch
+    timestamp: f64,
+}
+
+/// Records every tab it is attached to, and assembles the frames into an animated GIF per
+/// recording, so tabs running side by side don't end up in the same one.
+pub struct Screencast {
+    dir: PathBuf,
+    frames: Arc<Mutex<Vec<CapturedFrame>>>,
+}
+
+impl Screencast {
+    pub fn new(dir: &Path) -> Result<Self> {
+        fs::create_dir_all(dir)?;
+        Ok(Screencast {
+            dir: dir.to_path_buf(),
+            frames: Arc::new(Mutex::new(vec![])),
+        })
+    }
+
+    /// Records `tab` into the recording `name`, which ends up in `<name>.gif`.
+    pub fn record(&self, tab: &Arc<Tab>, name: &str) -> Result<()> {
+        let frames_dir = self.dir.join("frames").join(name);
+        fs::create_dir_all(&frames_dir)?;
+        let name = name.to_string();
+        let (tx, rx) = mpsc::channel::<(u32, String, Option<f64>)>();
+
+        // chrome stops sending frames until each one is acknowledged. acking from inside the
+        // event listener would block the thread that delivers the response, so hand the frames
+        // off to a worker instead.
+        let weak_tab = Arc::downgrade(tab);
+        let frames = self.frames.clone();
+        std::thread::spawn(move || {
+            for (session_id, data, timestamp) in rx {
+                let timestamp = timestamp.unwrap_or_else(now);
+                match BASE64_STANDARD.decode(data) {
+                    Ok(bytes) => {
+                        let path = frames_dir.join(format!("{:.3}.jpg", timestamp));
+                        match fs::write(&path, bytes) {
+                            Ok(_) => frames.lock().unwrap().push(CapturedFrame {
+                                name: name.clone(),
+                                path,
+                                timestamp,
+                            }),
+                            Err(e) => tracing::warn!("Unable to save screencast frame: {}", e),
+                        }
+                    }
+                    Err(e) => tracing::warn!("Invalid screencast frame: {}", e),
+                }
+
+                match weak_tab.upgrade() {
+                    Some(tab) => {
+                        let _ = tab.ack_screencast(session_id);
+                    }
+                    None => break,
+                }
+            }
+        });
+
+        tab.add_event_listener(Arc::new(move |event: &Event| {
+            if let Event::PageScreencastFrame(frame_event) = event {
+                let params = &frame_event.params;
+                let _ = tx.send((
+                    params.session_id,
+                    params.data.clone(),
+                    params.metadata.timestamp,
+                ));
+            }
+        }))?;
+
+        tab.start_screencast(
+            Some(StartScreencastFormatOption::Jpeg),
+            Some(80),
+            Some(1280),
+            Some(720),
+            Some(4),
+        )?;
+
+        Ok(())
+    }
+
+    /// Writes a GIF for each recording that captured anything, timing each frame by when it
+    /// was captured. Returns their paths.
+    pub fn finish(&self) -> Result<Vec<PathBuf>> {
+        let mut recordings: BTreeMap<String, Vec<CapturedFrame>> = BTreeMap::new();
+        for frame in std::mem::take(&mut *self.frames.lock().unwrap()) {
+            recordings
+                .entry(frame.name.clone())
+                .or_default()
+                .push(frame);
+        }
+        recordings
+            .into_iter()
+            .map(|(name, frames)| self.assemble(&name, frames))
+            .collect()
+    }
+
+    fn assemble(&self, name: &str, mut frames: Vec<CapturedFrame>) -> Result<PathBuf> {
+        frames.sort_by(|a, b| a.timestamp.total_cmp(&b.timestamp));
+
+        let output = self.dir.join(format!("{}.gif", name));
+        let mut encoder = GifEncoder::new_with_speed(BufWriter::new(File::create(&output)?), 10);
+        encoder
+            .set_repeat(Repeat::Infinite)
+            .map_err(anyhow::Error::from)?;
+
+        for (index, frame) in frames.iter().enumerate() {
+            let delay = frames
+                .get(index + 1)
+                .map(|next| Duration::from_secs_f64((next.timestamp - frame.timestamp).max(0.0)))
+                .unwrap_or(MAX_FRAME_DELAY)
+                .min(MAX_FRAME_DELAY);
+
+            let image = match image::load_from_memory_with_format(
+                &fs::read(&frame.path)?,
+                ImageFormat::Jpeg,
+            ) {
+                Ok(image) => image.to_rgba8(),
+                Err(e) => {
+                    tracing::warn!("Skipping frame {}: {}", frame.path.display(), e);
+                    continue;
+                }
+            };
+
+            encoder
+                .encode_frame(Frame::from_parts(
+                    image,
+                    0,
+                    0,
+                    Delay::from_saturating_duration(delay),
+                ))
+                .map_err(anyhow::Error::from)?;
+        }
+
+        Ok(output)
+    }
+}
+
+fn now() -> f64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs_f64())
+        .unwrap_or_default()
+}
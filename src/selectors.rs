@@ -0,0 +1,116 @@
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Every CSS selector the tool relies on. When the site changes its markup these can be
+/// overridden from a TOML file without waiting for a new release, i.e.:
+///
+/// ```toml
+/// solo_button = ".track .btn--solo"
+/// download_modal = ".modal .begin-download"
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Selectors {
+    // song page
+    pub mixer: String,
+    pub track_caption: String,
+    pub solo_button: String,
+    /// A solo button that is pressed
+    pub soloed_button: String,
+    pub mute_button: String,
+    /// A mute button that is pressed
+    pub muted_button: String,
+    pub mixer_reset_button: String,
+    pub download_button: String,
+    /// The download button when the song still has to be bought
+    pub add_to_cart_button: String,
+    pub precount_checkbox: String,
+
+    // download modal
+    pub download_modal: String,
+    pub download_link: String,
+    pub modal_close_button: String,
+
+    // pitch
+    pub pitch_value: String,
+    pub pitch_up_button: String,
+    pub pitch_down_button: String,
+    pub pitch_reload_link: String,
+
+    // account
+    pub login_link: String,
+    pub logout_link: String,
+    pub account_link: String,
+    pub login_form_username: String,
+    pub login_form_password: String,
+    pub login_form_submit: String,
+}
+
+impl Default for Selectors {
+    fn default() -> Self {
+        Selectors {
+            mixer: "div.mixer".to_string(),
+            track_caption: ".mixer .track .track__caption".to_string(),
+            solo_button: ".track__controls.track__solo".to_string(),
+            soloed_button: ".track__controls.track__solo.is-active".to_string(),
+            mute_button: ".track__controls.track__mute".to_string(),
+            muted_button: ".track__controls.track__mute.is-active".to_string(),
+            mixer_reset_button: "button.mixer__reset".to_string(),
+            download_button: "a.download".to_string(),
+            add_to_cart_button: "a.download.addtocart".to_string(),
+            precount_checkbox: "input#precount".to_string(),
+
+            download_modal: ".begin-download".to_string(),
+            download_link: "div.begin-download a".to_string(),
+            modal_close_button: "button.js-modal-close".to_string(),
+
+            pitch_value: "span.pitch__value".to_string(),
+            pitch_up_button: "div.pitch button.btn--pitch[title='Key up' i]".to_string(),
+            pitch_down_button: "div.pitch button.btn--pitch[title='Key down' i]".to_string(),
+            pitch_reload_link: "a#pitch-link".to_string(),
+
+            login_link: ".navigation a[href='/my/login.html']".to_string(),
+            logout_link: ".navigation a[href^='/logout.html']".to_string(),
+            account_link: ".navigation a[href='/my/index.html']".to_string(),
+            login_form_username: "#frm_login".to_string(),
+            login_form_password: "#frm_password".to_string(),
+            login_form_submit: "#sbm".to_string(),
+        }
+    }
+}
+
+impl Selectors {
+    /// Loads selector overrides from `path`, or from the default location if no path is given.
+    /// Selectors not mentioned in the file keep their built-in defaults.
+    pub fn load(path: Option<&Path>) -> Result<Self> {
+        let (path, required) = match path {
+            Some(path) => (path.to_path_buf(), true),
+            None => match Self::default_path() {
+                Some(path) => (path, false),
+                None => return Ok(Self::default()),
+            },
+        };
+
+        if !path.exists() {
+            if required {
+                return Err(anyhow!("Selectors file not found: {}", path.display()));
+            }
+            return Ok(Self::default());
+        }
+
+        tracing::debug!("Using selector overrides from {}", path.display());
+        Self::parse(&fs::read_to_string(&path)?)
+            .map_err(|e| anyhow!("Invalid selectors file {}: {}", path.display(), e))
+    }
+
+    pub fn parse(contents: &str) -> Result<Self> {
+        Ok(toml::from_str(contents)?)
+    }
+
+    /// `~/.config/kv-downloader/selectors.toml` on Linux, and the platform equivalent elsewhere.
+    pub fn default_path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("kv-downloader").join("selectors.toml"))
+    }
+}
@@ -0,0 +1,158 @@
+use headless_chrome::protocol::cdp::Network::{Cookie, CookieParam, CookieSameSite};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// A signed-in browser session that can be restored on a later run.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Session {
+    pub cookies: Vec<SessionCookie>,
+    #[serde(default)]
+    pub local_storage: BTreeMap<String, String>,
+    /// Unix timestamp (seconds) of when the session was saved
+    #[serde(default)]
+    pub saved_at: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SessionCookie {
+    pub name: String,
+    pub value: String,
+    pub domain: String,
+    pub path: String,
+    /// Unix timestamp (seconds), or a negative value for a session cookie
+    pub expires: f64,
+    pub secure: bool,
+    pub http_only: bool,
+    #[serde(default)]
+    pub same_site: Option<String>,
+}
+
+impl Session {
+    /// Builds a session from the browser cookies, keeping only the ones that belong to `domain`.
+    pub fn from_browser(
+        cookies: &[Cookie],
+        local_storage: BTreeMap<String, String>,
+        domain: &str,
+    ) -> Self {
+        Session {
+            cookies: cookies
+                .iter()
+                .filter(|c| cookie_matches_domain(&c.domain, domain))
+                .map(SessionCookie::from)
+                .collect(),
+            local_storage,
+            saved_at: now(),
+        }
+    }
+
+    /// Drops any cookies that have expired since the session was saved.
+    pub fn without_expired(mut self) -> Self {
+        let now = now() as f64;
+        self.cookies.retain(|c| !c.is_expired_at(now));
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cookies.is_empty()
+    }
+
+    pub fn cookie_params(&self) -> Vec<CookieParam> {
+        self.cookies.iter().map(SessionCookie::to_param).collect()
+    }
+
+    /// A script that restores local storage before any of the page's own scripts run.
+    pub fn local_storage_script(&self, domain: &str) -> Option<String> {
+        if self.local_storage.is_empty() {
+            return None;
+        }
+
+        let items = serde_json::to_string(&self.local_storage).ok()?;
+        let host = serde_json::to_string(domain).ok()?;
+        Some(format!(
+            r#"
+            (function() {{
+                if (location.hostname !== {host}) return;
+                const items = {items};
+                for (const [key, value] of Object.entries(items)) {{
+                    if (localStorage.getItem(key) === null) {{
+                        localStorage.setItem(key, value);
+                    }}
+                }}
+            }})();
+            "#
+        ))
+    }
+}
+
+impl SessionCookie {
+    pub fn is_expired_at(&self, now: f64) -> bool {
+        // session cookies report an expiry of -1
+        self.expires > 0.0 && self.expires <= now
+    }
+
+    pub fn to_param(&self) -> CookieParam {
+        let same_site = match self.same_site.as_deref() {
+            Some("Strict") => Some(CookieSameSite::Strict),
+            Some("Lax") => Some(CookieSameSite::Lax),
+            Some("None") => Some(CookieSameSite::None),
+            _ => None,
+        };
+
+        CookieParam {
+            name: self.name.clone(),
+            value: self.value.clone(),
+            url: None,
+            domain: Some(self.domain.clone()),
+            path: Some(self.path.clone()),
+            secure: Some(self.secure),
+            http_only: Some(self.http_only),
+            same_site,
+            expires: if self.expires > 0.0 {
+                Some(self.expires)
+            } else {
+                None
+            },
+            priority: None,
+            same_party: None,
+            source_scheme: None,
+            source_port: None,
+            partition_key: None,
+        }
+    }
+}
+
+impl From<&Cookie> for SessionCookie {
+    fn from(cookie: &Cookie) -> Self {
+        SessionCookie {
+            name: cookie.name.clone(),
+            value: cookie.value.clone(),
+            domain: cookie.domain.clone(),
+            path: cookie.path.clone(),
+            expires: if cookie.session { -1.0 } else { cookie.expires },
+            secure: cookie.secure,
+            http_only: cookie.http_only,
+            same_site: cookie.same_site.as_ref().map(|s| {
+                match s {
+                    CookieSameSite::Strict => "Strict",
+                    CookieSameSite::Lax => "Lax",
+                    CookieSameSite::None => "None",
+                }
+                .to_string()
+            }),
+        }
+    }
+}
+
+/// Whether a cookie set for `cookie_domain` would be sent to `host`.
+pub fn cookie_matches_domain(cookie_domain: &str, host: &str) -> bool {
+    let cookie_domain = cookie_domain.trim_start_matches('.');
+    host == cookie_domain || host.ends_with(&format!(".{}", cookie_domain))
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
@@ -0,0 +1,154 @@
+use crate::download_progress::DownloadProgress;
+use crate::driver::Driver;
+use crate::error::Result;
+use crate::session::SessionCookie;
+use crate::tasks::download_song::{DownloadError, DownloadOptions, DownloadReport};
+use headless_chrome::protocol::cdp::Network::CookieParam;
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::thread;
+
+/// How one song of a batch went
+pub struct SongOutcome {
+    pub url: String,
+    /// Where the song's tracks were saved
+    pub download_path: PathBuf,
+    pub result: Result<DownloadReport>,
+}
+
+impl Driver {
+    /// Downloads several songs, up to `jobs` at a time. Each song gets its own browser context,
+    /// so its downloads land in a directory of its own, named after the song, under the
+    /// download path. The contexts all start from the cookies of the current sign-in.
+    ///
+    /// A failing song doesn't stop the others. The outcomes are in the same order as `urls`.
+    pub fn download_songs(
+        &self,
+        urls: &[String],
+        options: &DownloadOptions,
+        jobs: usize,
+    ) -> Result<Vec<SongOutcome>> {
+        let base_dir = self.batch_dir()?;
+
+        // new contexts start out empty, so hand them the session we signed in with
+        let tab = self.browser.new_tab()?;
+        let cookies: Vec<_> = tab
+            .get_cookies()?
+            .iter()
+            .map(|cookie| SessionCookie::from(cookie).to_param())
+            .collect();
+        tab.close(false)?;
+
+        let queue = Mutex::new(urls.iter().enumerate().collect::<VecDeque<_>>());
+        let outcomes = Mutex::new(Vec::with_capacity(urls.len()));
+        let next = || queue.lock().unwrap().pop_front();
+
+        thread::scope(|scope| {
+            for _ in 0..jobs.clamp(1, urls.len().max(1)) {
+                scope.spawn(|| {
+                    while let Some((index, url)) = next() {
+                        let download_path = base_dir.join(song_slug(url));
+                        let _span = tracing::info_span!("song", song = %song_slug(url)).entered();
+                        let result = self.download_in_context(
+                            url,
+                            options.clone(),
+                            &download_path,
+                            cookies.clone(),
+                        );
+                        match &result {
+                            Ok(_) => tracing::info!("Finished {}", url),
+                            Err(e) => tracing::error!("Failed to download {}: {}", url, e),
+                        }
+                        outcomes.lock().unwrap().push((
+                            index,
+                            SongOutcome {
+                                url: url.clone(),
+                                download_path,
+                                result,
+                            },
+                        ));
+                    }
+                });
+            }
+        });
+
+        let mut outcomes = outcomes.into_inner().unwrap();
+        outcomes.sort_by_key(|(index, _)| *index);
+        Ok(outcomes.into_iter().map(|(_, outcome)| outcome).collect())
+    }
+
+    /// The directory the songs of a batch get a directory each in.
+    pub(crate) fn batch_dir(&self) -> Result<PathBuf> {
+        Ok(match &self.config.download_path {
+            Some(path) => PathBuf::from(path),
+            None => dirs::download_dir().ok_or_else(|| {
+                DownloadError::DownloadDirectory(
+                    "Could not determine the download directory, pass --download-path".to_string(),
+                )
+            })?,
+        })
+    }
+
+    fn download_in_context(
+        &self,
+        url: &str,
+        options: DownloadOptions,
+        download_path: &Path,
+        cookies: Vec<CookieParam>,
+    ) -> Result<DownloadReport> {
+        // chrome wants an absolute download path
+        std::fs::create_dir_all(download_path)?;
+        let download_path = download_path.canonicalize()?;
+        let progress = DownloadProgress::new_with_path(download_path.to_str());
+
+        let context = self.browser.new_context()?;
+        let tab = context.new_tab()?;
+        // songs run side by side, so each gets a screencast of its own
+        self.attach_recorders(&tab, &format!("recording-{}", song_slug(url)))?;
+        tab.set_cookies(cookies)?;
+
+        let result = self.download_song_in(
+            &tab,
+            url,
+            options,
+            Some(context.get_id()),
+            Some(download_path.to_string_lossy().into_owned()),
+            &progress,
+        );
+        if let Err(e) = tab.close(false) {
+            tracing::debug!("Unable to close the tab: {}", e);
+        }
+        result
+    }
+}
+
+/// A directory name for the song at `url`: the last segment of its path, without anything that
+/// isn't safe in a file name.
+pub fn song_slug(url: &str) -> String {
+    let path = url::Url::parse(url)
+        .ok()
+        .map(|url| url.path().to_string())
+        .unwrap_or_else(|| url.to_string());
+    let last = path
+        .trim_end_matches('/')
+        .rsplit('/')
+        .next()
+        .unwrap_or_default()
+        .trim_end_matches(".html");
+    let slug: String = last
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '-'
+            }
+        })
+        .collect();
+    if slug.is_empty() {
+        "song".to_string()
+    } else {
+        slug
+    }
+}
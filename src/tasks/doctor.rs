@@ -0,0 +1,119 @@
+use crate::driver::{Driver, TabExt};
+use crate::error::Result;
+use headless_chrome::Tab;
+use std::time::Duration;
+
+pub struct SelectorCheck {
+    pub name: &'static str,
+    pub selector: String,
+    /// `None` if the selector is invalid CSS
+    pub matches: Option<usize>,
+    /// Whether the tool can't work without at least one match
+    pub required: bool,
+}
+
+impl SelectorCheck {
+    pub fn is_ok(&self) -> bool {
+        !self.required || self.matches.unwrap_or(0) > 0
+    }
+}
+
+pub struct DoctorReport {
+    pub signed_in: bool,
+    pub song_page: Vec<SelectorCheck>,
+    pub login_page: Vec<SelectorCheck>,
+    pub track_names: usize,
+    pub solo_buttons: usize,
+}
+
+impl DoctorReport {
+    pub fn is_ok(&self) -> bool {
+        self.signed_in
+            && self.track_names > 0
+            && self.track_names == self.solo_buttons
+            && self.song_page.iter().all(SelectorCheck::is_ok)
+            && self.login_page.iter().all(SelectorCheck::is_ok)
+    }
+}
+
+impl Driver {
+    /// Loads a song page (the driver should already be signed in) and the login page, and checks
+    /// every selector the tool depends on against them.
+    pub fn check_selectors(&self, url: &str) -> Result<DoctorReport> {
+        let sel = &self.config.selectors;
+
+        let tab = self.new_tab()?;
+        tab.navigate(url)?;
+        // give the mixer a moment to render its tracks
+        let _ = tab.wait_for(&sel.solo_button, Duration::from_secs(15));
+
+        let signed_in = self.is_signed_in(&tab);
+        let song_page = vec![
+            Self::check(&tab, "mixer", &sel.mixer, true),
+            Self::check(&tab, "track names", &sel.track_caption, true),
+            Self::check(&tab, "solo buttons", &sel.solo_button, true),
+            Self::check(&tab, "download button", &sel.download_button, true),
+            Self::check(&tab, "add to cart button", &sel.add_to_cart_button, false),
+            Self::check(&tab, "precount checkbox", &sel.precount_checkbox, true),
+            Self::check(&tab, "pitch value", &sel.pitch_value, true),
+            Self::check(&tab, "pitch up button", &sel.pitch_up_button, true),
+            Self::check(&tab, "pitch down button", &sel.pitch_down_button, true),
+            Self::check(&tab, "pitch reload link", &sel.pitch_reload_link, true),
+            Self::check(&tab, "account link", &sel.account_link, true),
+            Self::check(&tab, "logout link", &sel.logout_link, true),
+            Self::check(&tab, "mute buttons", &sel.mute_button, true),
+            Self::check(&tab, "mixer reset button", &sel.mixer_reset_button, true),
+        ];
+        let track_names = song_page[1].matches.unwrap_or(0);
+        let solo_buttons = song_page[2].matches.unwrap_or(0);
+        tab.close(false)?;
+
+        // the login form is only shown when signed out, so look at it from a fresh context
+        let context = self.browser.new_context()?;
+        let tab = context.new_tab()?;
+        tab.navigate(&format!("https://{}", self.config.domain))?;
+        let login_link = Self::check(&tab, "login link", &sel.login_link, true);
+        tab.navigate(&format!("https://{}/my/login.html", self.config.domain))?;
+        let login_page = vec![
+            login_link,
+            Self::check(&tab, "login username", &sel.login_form_username, true),
+            Self::check(&tab, "login password", &sel.login_form_password, true),
+            Self::check(&tab, "login submit", &sel.login_form_submit, true),
+        ];
+        tab.close(false)?;
+
+        Ok(DoctorReport {
+            signed_in,
+            song_page,
+            login_page,
+            track_names,
+            solo_buttons,
+        })
+    }
+
+    fn check(tab: &Tab, name: &'static str, selector: &str, required: bool) -> SelectorCheck {
+        SelectorCheck {
+            name,
+            selector: selector.to_string(),
+            matches: Self::count_matches(tab, selector),
+            required,
+        }
+    }
+
+    /// Counts matches in the page itself, so that an invalid selector is told apart from one
+    /// that simply has no matches.
+    fn count_matches(tab: &Tab, selector: &str) -> Option<usize> {
+        let selector = serde_json::to_string(selector).ok()?;
+        tab.evaluate(
+            &format!(
+                "(() => {{ try {{ return document.querySelectorAll({}).length; }} catch (e) {{ return -1; }} }})()",
+                selector
+            ),
+            false,
+        )
+        .ok()?
+        .value
+        .and_then(|v| v.as_i64())
+        .and_then(|n| usize::try_from(n).ok())
+    }
+}
@@ -1,23 +1,80 @@
-use crate::driver::Driver;
+use crate::download_progress::DownloadProgress;
+use crate::downloads::{DownloadState, DownloadWatcher};
+use crate::driver::{is_clickable, Driver, TabExt, POLL_INTERVAL};
+use crate::error::{KvError, Result};
+use crate::events::Event;
+use crate::http_download;
+use crate::links::TrackLink;
+use crate::mixer::MixerState;
+use crate::session::cookie_matches_domain;
+use crate::wait;
 
-use anyhow::{anyhow, Result};
-use base64::prelude::BASE64_STANDARD;
-use base64::Engine;
-use headless_chrome::protocol::cdp::types::Event;
-use headless_chrome::protocol::cdp::Page::StartScreencastFormatOption;
 use headless_chrome::{Element, Tab};
+use serde::Serialize;
+use std::cell::Cell;
+use std::collections::VecDeque;
 use std::fmt::Display;
-use std::path::PathBuf;
-use std::sync::Arc;
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Arc};
+use std::thread;
+use std::time::Instant;
 use std::{error::Error, thread::sleep, time::Duration};
 
-/// Maximum time to wait for a download to complete (in seconds)
-const DOWNLOAD_COMPLETION_TIMEOUT_SECS: u64 = 300; // 5 minutes
+/// How long a button may take to become clickable, or the modal to close
+const STEP_TIMEOUT: Duration = Duration::from_secs(15);
 
-#[derive(Default)]
+/// How long the mixer may take to show a solo click
+const SOLO_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// How long the mixer may take to load its tracks, including after a pitch change
+const MIXER_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How often the progress of a download is reported
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(250);
+
+/// How many tracks may still be transferring while the next one is generated
+const MAX_TRANSFERS: usize = 3;
+
+#[derive(Debug, Default, Clone)]
 pub struct DownloadOptions {
     pub count_in: bool,
     pub transpose: i8,
+    pub mode: DownloadMode,
+    /// Ignore the tracks a previous run already downloaded
+    pub force_restart: bool,
+}
+
+/// What downloading a song achieved
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DownloadReport {
+    pub song: String,
+    /// Tracks saved by this run
+    pub downloaded: Vec<DownloadedTrack>,
+    /// Tracks a previous run had already downloaded
+    pub skipped: Vec<String>,
+    /// The download links, in [`DownloadMode::LinksOnly`]
+    pub links: Vec<TrackLink>,
+    /// Tracks that failed every attempt
+    pub failed: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DownloadedTrack {
+    pub track: String,
+    /// Where the file was saved, if we know where the browser put it
+    pub path: Option<PathBuf>,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DownloadMode {
+    /// Let the browser save each file
+    #[default]
+    Browser,
+    /// Fetch each file over HTTP with the browser's cookies
+    Direct,
+    /// Generate each file but only collect its download link
+    LinksOnly,
 }
 
 #[derive(Debug)]
@@ -25,6 +82,26 @@ pub enum DownloadError {
     NotPurchased,
     NotASongPage,
     HumanVerificationRequired,
+    ModalTimeout(Duration),
+    StartTimeout(Duration),
+    CompletionTimeout(Duration),
+    Canceled(String),
+    InvalidDownloadLink(String),
+    DownloadDirectory(String),
+    Http(String),
+    SizeMismatch {
+        expected: u64,
+        received: u64,
+    },
+    PitchNotSet(i8),
+    /// The mixer kept other tracks audible besides this one
+    SoloNotSet {
+        track: String,
+        mixer: String,
+    },
+    /// Some tracks failed; the report has what was saved all the same
+    TracksFailed(Box<DownloadReport>),
+    SongsFailed(Vec<String>),
 }
 
 impl Display for DownloadError {
@@ -32,295 +109,769 @@ impl Display for DownloadError {
         match self {
             Self::NotPurchased => f.write_str("This track has not been purchased"),
             Self::NotASongPage => f.write_str("This doesn't look like a song page. Check the url."),
-            Self::HumanVerificationRequired => f.write_str("The headless browser was detected as a bot and is being presented with a 'Verify you are human' step. Try running without --headless.")
+            Self::HumanVerificationRequired => f.write_str("The browser was detected as a bot and is being presented with a 'Verify you are human' step. Run it in a terminal, without --no-interactive or --output json, to complete it in a browser window."),
+            Self::ModalTimeout(timeout) => write!(f, "Timed out waiting for download modal after {:?}", timeout),
+            Self::StartTimeout(timeout) => write!(f, "Download did not start within {:?}", timeout),
+            Self::CompletionTimeout(timeout) => write!(f, "Download did not complete within {:?}", timeout),
+            Self::Canceled(filename) => write!(f, "The download of {} was cancelled", filename),
+            Self::InvalidDownloadLink(msg) => write!(f, "Invalid download link: {}", msg),
+            Self::DownloadDirectory(msg) => f.write_str(msg),
+            Self::Http(msg) => write!(f, "Download failed: {}", msg),
+            Self::SizeMismatch { expected, received } => write!(f, "Expected {} bytes but received {}", expected, received),
+            Self::PitchNotSet(pitch) => write!(f, "Failed to set pitch to {}", pitch),
+            Self::SoloNotSet { track, mixer } => write!(f, "Couldn't solo '{}' in the mixer ({})", track, mixer),
+            Self::TracksFailed(report) => write!(f, "{} tracks failed to download", report.failed.len()),
+            Self::SongsFailed(songs) => write!(f, "{} songs failed to download", songs.len()),
         }
     }
 }
 impl Error for DownloadError {}
 
+/// What happens to each track once its download has been generated
+enum Saver {
+    Browser(DownloadWatcher),
+    Direct,
+    LinksOnly,
+}
+
+/// A track whose file has been generated, on its way to disk
+enum Transfer {
+    /// The browser is saving the download with this guid
+    Browser { guid: String },
+    Direct {
+        request: http_download::Request,
+        dest: PathBuf,
+    },
+    /// Nothing to transfer, only the link is kept
+    Link(TrackLink),
+}
+
+/// What became of a track once it's done
+enum Saved {
+    /// Downloaded to this path, if we know where the browser put it
+    File(Option<PathBuf>),
+    Link(TrackLink),
+}
+
+/// Where the tracks of a song are up to
+#[derive(Default)]
+struct TrackQueue {
+    /// Tracks still to generate, by index, with the attempt each one is on
+    pending: VecDeque<(usize, u32)>,
+    downloaded: Vec<DownloadedTrack>,
+    links: Vec<TrackLink>,
+    failed: Vec<String>,
+}
+
+/// The controls of a song's mixer, by track
+struct Mixer<'a> {
+    track_names: Vec<String>,
+    solo_buttons: Vec<Element<'a>>,
+    download_button: Element<'a>,
+    /// Whether the pressed solo buttons can be read, and so the solo checked. Turned off for
+    /// the rest of the song the first time none can be.
+    check_solo: Cell<bool>,
+}
+
+/// One song being downloaded: what happens to its tracks and where they go
+struct Song<'a> {
+    url: &'a str,
+    saver: Saver,
+    download_path: Option<String>,
+    progress: &'a DownloadProgress,
+}
+
 impl Driver {
-    pub fn download_song(&self, url: &str, options: DownloadOptions) -> Result<()> {
-        // Set the URL in progress tracking
-        self.progress.set_url(url)?;
+    /// Downloads every track of the song. In [`DownloadMode::LinksOnly`] nothing is saved and
+    /// the tracks' download links are collected in the report instead.
+    pub fn download_song(&self, url: &str, options: DownloadOptions) -> Result<DownloadReport> {
+        let tab = self.new_tab()?;
+        self.download_song_in(
+            &tab,
+            url,
+            options,
+            None,
+            self.config.download_path.clone(),
+            &self.progress,
+        )
+    }
+
+    /// Downloads the song at `url` on `tab`, which was opened in `browser_context_id`, into
+    /// `download_path`, keeping track of which tracks are done in `progress`.
+    pub(crate) fn download_song_in(
+        &self,
+        tab: &Arc<Tab>,
+        url: &str,
+        options: DownloadOptions,
+        browser_context_id: Option<&str>,
+        download_path: Option<String>,
+        progress: &DownloadProgress,
+    ) -> Result<DownloadReport> {
+        // collecting links doesn't download anything, so it leaves the progress alone
+        if options.mode != DownloadMode::LinksOnly {
+            progress.start(url, options.force_restart)?;
+        }
 
-        let tab = self.browser.new_tab()?;
         tab.set_default_timeout(Duration::from_secs(30));
 
-        tab.navigate_to(url)?.wait_until_navigated()?;
+        let saver = match options.mode {
+            DownloadMode::Browser => {
+                if let Some(download_path) = &download_path {
+                    tracing::info!("Setting download path to: {}", download_path);
+                }
+                Saver::Browser(DownloadWatcher::watch(
+                    tab,
+                    browser_context_id,
+                    download_path.as_deref(),
+                )?)
+            }
+            DownloadMode::Direct => {
+                DownloadWatcher::deny(tab, browser_context_id)?;
+                Saver::Direct
+            }
+            DownloadMode::LinksOnly => {
+                DownloadWatcher::deny(tab, browser_context_id)?;
+                Saver::LinksOnly
+            }
+        };
+        let song = Song {
+            url,
+            saver,
+            download_path,
+            progress,
+        };
 
-        if !self.is_a_song_page(&tab) {
-            tab.stop_screencast()?;
+        self.download_song_on_tab(tab, &song, url, options)
+            .map_err(|e| self.with_diagnostics(tab, "download", e))
+            .inspect_err(|e| {
+                // failing tracks are reported as they happen
+                if !matches!(e.kind(), KvError::Download(DownloadError::TracksFailed(_))) {
+                    self.emit(Event::SongFailed {
+                        song: url.to_string(),
+                        error: e.to_string(),
+                    });
+                }
+            })
+    }
 
-            if self.is_verify_you_are_human_page(&tab) {
-                return Err(anyhow!(DownloadError::HumanVerificationRequired));
+    fn download_song_on_tab(
+        &self,
+        tab: &Tab,
+        song: &Song,
+        url: &str,
+        options: DownloadOptions,
+    ) -> Result<DownloadReport> {
+        tab.navigate(url)?;
+
+        if !self.is_a_song_page(tab) {
+            if self.is_verify_you_are_human_page(tab) {
+                self.complete_human_verification(tab, url)?;
             } else {
-                return Err(anyhow!(DownloadError::NotASongPage));
+                return Err(DownloadError::NotASongPage.into());
             }
         }
 
-        if !self.is_downloadable(&tab) {
-            tab.stop_screencast()?;
-            return Err(anyhow!(DownloadError::NotPurchased));
+        if !self.is_downloadable(tab) {
+            return Err(DownloadError::NotPurchased.into());
         }
 
+        let sel = &self.config.selectors;
         if options.count_in {
-            let el = tab
-                .wait_for_element_with_custom_timeout("input#precount", Duration::from_secs(15))?;
+            let el = tab.wait_for(&sel.precount_checkbox, Duration::from_secs(15))?;
             if !el.is_checked() {
                 el.click()?;
             }
         }
 
-        self.adjust_pitch(options.transpose, &tab)?;
-
-        self.solo_and_download_tracks(&tab)?;
-
-        tab.stop_screencast()?;
+        self.adjust_pitch(song.url, options.transpose, tab)?;
 
-        Ok(())
+        self.solo_and_download_tracks(tab, song)
     }
 
-    fn solo_and_download_tracks(&self, tab: &Tab) -> Result<()> {
-        let solo_button_sel = ".track__controls.track__solo";
-        let solo_buttons = tab.find_elements(solo_button_sel)?;
-        let download_button = tab.find_element("a.download")?;
-        let track_names = Driver::extract_track_names(tab)?;
+    fn solo_and_download_tracks(&self, tab: &Tab, song: &Song) -> Result<DownloadReport> {
+        let links_only = matches!(song.saver, Saver::LinksOnly);
+        let sel = &self.config.selectors;
+        let mixer = Mixer {
+            solo_buttons: tab.find_all(&sel.solo_button)?,
+            download_button: tab.find(&sel.download_button)?,
+            track_names: self.extract_track_names(tab)?,
+            check_solo: Cell::new(true),
+        };
+        let track_names = &mixer.track_names;
 
         tab.enable_debugger()?;
-        sleep(Duration::from_secs(2));
-
-        let mut failed_tracks = Vec::new();
-
-        for (index, solo_btn) in solo_buttons.iter().enumerate() {
-            let track_name = track_names[index].clone();
+        self.wait_until("the mixer to load", MIXER_TIMEOUT, || {
+            is_clickable(&mixer.download_button)
+        })?;
 
+        let mut tracks = TrackQueue::default();
+        let mut already_downloaded = vec![];
+        for (index, track_name) in track_names.iter().enumerate() {
             // Check if track was already downloaded
-            if self.progress.is_track_downloaded(&track_name)? {
-                tracing::info!(
-                    "Skipping track {} '{}' (already downloaded)",
-                    index + 1,
-                    track_name
-                );
+            if !links_only && song.progress.is_track_downloaded(track_name)? {
+                already_downloaded.push(track_name.clone());
                 continue;
             }
+            tracks.pending.push_back((index, 1));
+        }
+        self.emit(Event::SongLoaded {
+            song: song.url.to_string(),
+            title: tab.get_title().ok(),
+            tracks: track_names.clone(),
+            already_downloaded: already_downloaded.clone(),
+        });
+
+        // the page can only generate one track at a time, but the files it hands over are
+        // transferred in the background while the next track is generated
+        let (done_tx, done_rx) = mpsc::channel::<(usize, u32, Result<Saved>)>();
+        thread::scope(|scope| -> Result<()> {
+            let mut transferring = 0;
+            loop {
+                // take in the transfers that are done, waiting for one when there is nothing
+                // else to do or enough of them are in flight already
+                loop {
+                    let wait = transferring > 0
+                        && (tracks.pending.is_empty() || transferring >= MAX_TRANSFERS);
+                    let done = if wait {
+                        done_rx.recv().ok()
+                    } else {
+                        done_rx.try_recv().ok()
+                    };
+                    let Some((index, attempt, result)) = done else {
+                        break;
+                    };
+                    transferring -= 1;
+                    self.settle_track(
+                        song,
+                        &mut tracks,
+                        &track_names[index],
+                        index,
+                        attempt,
+                        result,
+                    )?;
+                }
 
-            tracing::info!("Processing track {} '{}'", index + 1, track_name);
-
-            // Try downloading with retries
-            let mut attempts = 0;
-            let max_attempts = 3;
-            let mut download_successful = false;
-
-            while attempts < max_attempts && !download_successful {
-                attempts += 1;
-
-                match self.download_single_track(
-                    tab,
-                    solo_btn,
-                    &download_button,
-                    &track_name,
-                    attempts,
-                ) {
-                    Ok(_) => {
-                        download_successful = true;
-                        tracing::info!("- '{}' complete!", track_name);
-                        self.progress.mark_track_downloaded(&track_name)?;
+                let Some((index, attempt)) = tracks.pending.pop_front() else {
+                    break;
+                };
+                let track_name = &track_names[index];
+                if attempt > 1 {
+                    let wait_time = self
+                        .config
+                        .retry
+                        .backoff_with_jitter(attempt - 1, fastrand::f64());
+                    self.emit(Event::RetryScheduled {
+                        song: song.url.to_string(),
+                        track: track_name.clone(),
+                        attempt,
+                        wait_secs: wait_time.as_secs_f64(),
+                    });
+                    sleep(wait_time);
+                }
+                // pause before the track starts, so it shows as started once the pause is over
+                self.rate_limiter.acquire(|until, reason| {
+                    self.emit(Event::RateLimited {
+                        song: song.url.to_string(),
+                        track: track_name.to_string(),
+                        until: until.to_rfc3339_opts(chrono::SecondsFormat::Secs, false),
+                        reason: reason.to_string(),
+                    })
+                })?;
+                self.emit(Event::TrackStarted {
+                    song: song.url.to_string(),
+                    track: track_name.clone(),
+                    attempt,
+                });
+
+                let generated = self
+                    .generate_track(tab, song, &mixer, index, attempt)
+                    .map_err(|e| {
+                        self.with_diagnostics(
+                            tab,
+                            &format!("track-{}-attempt-{}", index + 1, attempt),
+                            e,
+                        )
+                    });
+                match generated {
+                    Ok(Transfer::Link(link)) => self.settle_track(
+                        song,
+                        &mut tracks,
+                        track_name,
+                        index,
+                        attempt,
+                        Ok(Saved::Link(link)),
+                    )?,
+                    Ok(transfer) => {
+                        let span = tracing::info_span!("track", name = %track_name);
+                        let done_tx = done_tx.clone();
+                        transferring += 1;
+                        scope.spawn(move || {
+                            let _span = span.entered();
+                            let result = self
+                                .finish_transfer(song, track_name, transfer)
+                                .map(Saved::File);
+                            // the receiver only goes away on an error that ends the song
+                            let _ = done_tx.send((index, attempt, result));
+                        });
                     }
                     Err(e) => {
-                        tracing::warn!("Attempt {} failed for '{}': {}", attempts, track_name, e);
-                        if attempts < max_attempts {
-                            let wait_time = Duration::from_secs(5 * attempts as u64);
-                            tracing::info!("Waiting {:?} before retry...", wait_time);
-                            sleep(wait_time);
-                        }
+                        self.settle_track(song, &mut tracks, track_name, index, attempt, Err(e))?
                     }
                 }
             }
-
-            if !download_successful {
-                failed_tracks.push(track_name.clone());
-                tracing::error!(
-                    "Failed to download '{}' after {} attempts",
-                    track_name,
-                    max_attempts
-                );
-            }
-        }
-
-        if failed_tracks.is_empty() {
+            Ok(())
+        })?;
+
+        let report = DownloadReport {
+            song: song.url.to_string(),
+            downloaded: tracks.downloaded,
+            skipped: already_downloaded,
+            links: tracks.links,
+            failed: tracks.failed,
+        };
+        self.emit(Event::song_completed(&report));
+        if report.failed.is_empty() && links_only {
+            tracing::info!(
+                "Done! Collected links for all {} tracks",
+                report.links.len()
+            );
+        } else if report.failed.is_empty() {
             tracing::info!(
                 "Done! All tracks downloaded successfully: {}\n - ",
                 track_names.join("\n - ")
             );
             // Clear progress file on successful completion
-            self.progress.clear()?;
+            song.progress.clear()?;
             tracing::info!("Progress file cleared");
         } else {
             tracing::warn!(
                 "Download completed with {} failures. Failed tracks:\n - {}",
-                failed_tracks.len(),
-                failed_tracks.join("\n - ")
+                report.failed.len(),
+                report.failed.join("\n - ")
             );
-            tracing::info!("Progress saved. Run the command again to retry failed tracks.");
-            return Err(anyhow!("{} tracks failed to download", failed_tracks.len()));
+            if !links_only {
+                tracing::info!("Progress saved. Run the command again to retry failed tracks.");
+            }
         }
 
-        Ok(())
+        if !report.failed.is_empty() {
+            return Err(DownloadError::TracksFailed(Box::new(report)).into());
+        }
+        Ok(report)
     }
 
-    fn download_single_track(
+    /// Records how an attempt at a track went, queueing another attempt if it failed and there
+    /// are attempts left.
+    fn settle_track(
         &self,
-        tab: &Tab,
-        solo_btn: &Element,
-        download_button: &Element,
+        song: &Song,
+        tracks: &mut TrackQueue,
         track_name: &str,
+        index: usize,
         attempt: u32,
+        result: Result<Saved>,
     ) -> Result<()> {
-        if attempt > 1 {
-            tracing::info!("Attempt {} for track '{}'", attempt, track_name);
+        match result {
+            Ok(saved) => {
+                let path = match saved {
+                    Saved::Link(link) => {
+                        tracks.links.push(link);
+                        None
+                    }
+                    Saved::File(path) => {
+                        song.progress.mark_track_downloaded(track_name)?;
+                        tracks.downloaded.push(DownloadedTrack {
+                            track: track_name.to_string(),
+                            path: path.clone(),
+                        });
+                        path
+                    }
+                };
+                self.emit(Event::TrackCompleted {
+                    song: song.url.to_string(),
+                    track: track_name.to_string(),
+                    path,
+                });
+            }
+            Err(e) => {
+                let retrying = e.is_retryable() && attempt < self.config.retry.max_attempts.max(1);
+                self.emit(Event::TrackFailed {
+                    song: song.url.to_string(),
+                    track: track_name.to_string(),
+                    attempt,
+                    error: e.to_string(),
+                    retrying,
+                });
+                if !e.is_retryable() {
+                    return Err(e);
+                }
+                if retrying {
+                    tracks.pending.push_front((index, attempt + 1));
+                } else {
+                    tracks.failed.push(track_name.to_string());
+                }
+            }
         }
+        Ok(())
+    }
 
-        solo_btn.scroll_into_view()?;
-        sleep(Duration::from_millis(500));
-        solo_btn.click()?;
-        sleep(Duration::from_millis(500));
+    /// Solos the track at `index` and has the site generate its file, then hands the file over
+    /// for transfer.
+    fn generate_track(
+        &self,
+        tab: &Tab,
+        song: &Song,
+        mixer: &Mixer,
+        index: usize,
+        attempt: u32,
+    ) -> Result<Transfer> {
+        let track_name = &mixer.track_names[index];
+        let download_button = &mixer.download_button;
+        self.click_solo(&mixer.solo_buttons[index])?;
+
+        // the previous track's solo is expected to be released by this one's, check before the
+        // wrong mix gets generated
+        self.ensure_solo(tab, mixer, index)?;
+        self.emit(Event::TrackSoloed {
+            song: song.url.to_string(),
+            track: track_name.to_string(),
+            attempt,
+        });
 
         tracing::info!("- starting download...");
+        let started = match &song.saver {
+            Saver::Browser(downloads) => downloads.started(),
+            _ => 0,
+        };
         download_button.scroll_into_view()?;
-        sleep(Duration::from_millis(500));
+        self.wait_until("the download button", STEP_TIMEOUT, || {
+            is_clickable(download_button)
+        })?;
         download_button.click()?;
-        sleep(Duration::from_millis(500));
 
         tracing::info!("- waiting for download modal...");
 
         // Increase timeout for retries
-        let timeout = Duration::from_secs(60 + (attempt as u64 - 1) * 30);
-        tab.wait_for_element_with_custom_timeout(".begin-download", timeout)
-            .map_err(|_| anyhow!("Timed out waiting for download modal after {:?}", timeout))?;
-
-        // Wait a bit for the modal to be fully rendered
-        sleep(Duration::from_secs(1));
-
-        // Extract the filename from the download link before closing the modal
-        let filename = self.extract_download_filename(tab)?;
-        tracing::debug!("Expected download filename: {}", filename);
-
-        // Try to find and close the modal
-        match tab.find_element("button.js-modal-close") {
-            Ok(close_btn) => {
-                close_btn.click()?;
+        let timeout = self.config.retry.modal_timeout(attempt);
+        tab.wait_for_element_with_custom_timeout(&self.config.selectors.download_modal, timeout)
+            .map_err(|_| DownloadError::ModalTimeout(timeout))?;
+        self.emit(Event::ModalAppeared {
+            song: song.url.to_string(),
+            track: track_name.to_string(),
+            attempt,
+        });
+
+        let transfer = match &song.saver {
+            Saver::Browser(downloads) => {
+                // a download from an attempt that timed out can still begin after this click,
+                // so only claim the one for the modal's link
+                let link = self
+                    .extract_download_link(tab)
+                    .inspect_err(|e| {
+                        tracing::debug!(
+                            "Couldn't read the download link ({}), going by the track name",
+                            e
+                        )
+                    })
+                    .ok();
+                let policy = &self.config.retry;
+                let download = downloads
+                    .wait_for_start(
+                        started,
+                        link.as_ref(),
+                        track_name,
+                        policy.download_start_timeout,
+                    )
+                    .ok_or(DownloadError::StartTimeout(policy.download_start_timeout))?;
+                tracing::debug!("Download {} started: {}", download.guid, download.filename);
+                Transfer::Browser {
+                    guid: download.guid,
+                }
             }
-            Err(_) => {
-                tracing::warn!("Could not find modal close button, proceeding anyway");
+            Saver::Direct => {
+                let (filename, request) = self.download_request(tab)?;
+                Transfer::Direct {
+                    dest: Self::download_dir(song)?.join(filename),
+                    request,
+                }
             }
-        }
-        sleep(Duration::from_secs(4));
+            Saver::LinksOnly => Transfer::Link(self.collect_link(tab, track_name)?),
+        };
 
-        // Wait for the download to complete
-        self.wait_for_download_completion(&filename)?;
+        self.close_download_modal(tab)?;
+        Ok(transfer)
+    }
 
+    fn click_solo(&self, solo_btn: &Element) -> Result<()> {
+        solo_btn.scroll_into_view()?;
+        self.wait_until("the solo button", STEP_TIMEOUT, || is_clickable(solo_btn))?;
+        solo_btn.click()?;
         Ok(())
     }
 
-    fn extract_download_filename(&self, tab: &Tab) -> Result<String> {
-        // Try to find the download link in the modal
-        let download_link = tab.find_element("div.begin-download a")?;
-
-        // Get the href attribute which should contain the filename
-        let href = download_link
-            .get_attribute_value("href")?
-            .ok_or_else(|| anyhow!("Download link has no href attribute"))?;
+    /// Makes sure the track at `index` is the only one that can be heard, resetting the mixer
+    /// and soloing the track again when it isn't.
+    fn ensure_solo(&self, tab: &Tab, mixer: &Mixer, index: usize) -> Result<()> {
+        if !mixer.check_solo.get() {
+            return Ok(());
+        }
+        let track_name = &mixer.track_names[index];
+        let state = self.wait_for_solo(tab, index, SOLO_TIMEOUT)?;
+        if state.is_solo(index) {
+            return Ok(());
+        }
+        tracing::warn!(
+            "The mixer isn't soloing '{}' ({}), resetting it",
+            track_name,
+            state.describe(&mixer.track_names)
+        );
 
-        // Extract filename from the URL
-        let filename = href
-            .split('/')
-            .last()
-            .ok_or_else(|| anyhow!("Could not extract filename from URL"))?
-            .to_string();
+        // the reset button is disabled while there is nothing to reset
+        let reset = tab.find(&self.config.selectors.mixer_reset_button)?;
+        if is_clickable(&reset)? {
+            reset.click()?;
+            self.wait_until("the mixer to reset", STEP_TIMEOUT, || {
+                Ok(self.mixer_state(tab)?.is_reset())
+            })?;
+        }
+        self.click_solo(&mixer.solo_buttons[index])?;
 
-        // Decode URL-encoded characters
-        let decoded = urlencoding::decode(&filename)
-            .map_err(|e| anyhow!("Failed to decode filename: {}", e))?
-            .to_string();
+        let state = self.wait_for_solo(tab, index, STEP_TIMEOUT)?;
+        if state.is_solo(index) {
+            return Ok(());
+        }
+        let mixer_state = state.describe(&mixer.track_names);
+        if !state.soloed.contains(&true) {
+            // no solo shows up at all even after soloing from scratch, so the pressed state
+            // can't be read rather than being wrong. Don't pay for finding that out again.
+            mixer.check_solo.set(false);
+            tracing::warn!(
+                "Unable to tell which tracks are soloed, so the solo won't be checked for the rest of this song. Check the soloed_button selector."
+            );
+            return Ok(());
+        }
+        tracing::warn!("Mixer state after resetting: {}", mixer_state);
+        Err(DownloadError::SoloNotSet {
+            track: track_name.to_string(),
+            mixer: mixer_state,
+        }
+        .into())
+    }
 
-        Ok(decoded)
+    /// Waits up to `timeout` for the track at `index` to be the only one audible, returning
+    /// the mixer as it was last seen.
+    fn wait_for_solo(&self, tab: &Tab, index: usize, timeout: Duration) -> Result<MixerState> {
+        let mut state = MixerState::default();
+        wait::poll(timeout, POLL_INTERVAL, || {
+            state = self.mixer_state(tab)?;
+            Ok::<_, KvError>(state.is_solo(index))
+        })?;
+        Ok(state)
     }
 
-    fn wait_for_download_completion(&self, expected_filename: &str) -> Result<()> {
-        let download_path = match &self.config.download_path {
-            Some(path) => PathBuf::from(path),
-            None => Self::get_default_download_dir()?,
+    /// Waits for a file handed over by [`Driver::generate_track`] to be saved, returning where
+    /// it was saved if we know.
+    fn finish_transfer(
+        &self,
+        song: &Song,
+        track_name: &str,
+        transfer: Transfer,
+    ) -> Result<Option<PathBuf>> {
+        let mut last_reported: Option<Instant> = None;
+        let on_progress = |received, total| {
+            let due = last_reported.is_none_or(|at| at.elapsed() >= PROGRESS_INTERVAL);
+            if !due && Some(received) != total {
+                return;
+            }
+            last_reported = Some(Instant::now());
+            self.emit(Event::TrackProgress {
+                song: song.url.to_string(),
+                track: track_name.to_string(),
+                received,
+                total,
+            });
         };
+        let path = match (transfer, &song.saver) {
+            (Transfer::Browser { guid }, Saver::Browser(downloads)) => {
+                let filename = self.wait_for_browser_download(downloads, &guid, on_progress)?;
+                // the browser saves to its own default folder when not given one
+                song.download_path
+                    .as_deref()
+                    .map(PathBuf::from)
+                    .or_else(dirs::download_dir)
+                    .map(|dir| dir.join(filename))
+            }
+            (Transfer::Direct { request, dest }, _) => {
+                Self::fetch_directly(&request, &dest, on_progress)?;
+                Some(dest)
+            }
+            _ => None,
+        };
+        self.emit(Event::TrackVerifying {
+            song: song.url.to_string(),
+            track: track_name.to_string(),
+        });
+        Ok(path)
+    }
 
+    /// Waits for the browser to finish saving the download with `guid`, returning the name of
+    /// the file.
+    fn wait_for_browser_download(
+        &self,
+        downloads: &DownloadWatcher,
+        guid: &str,
+        mut on_progress: impl FnMut(u64, Option<u64>),
+    ) -> Result<String> {
+        let policy = &self.config.retry;
         tracing::info!("- waiting for download to complete...");
-        tracing::debug!("Monitoring directory: {}", download_path.display());
-
-        let start = std::time::Instant::now();
-        let timeout = Duration::from_secs(DOWNLOAD_COMPLETION_TIMEOUT_SECS);
-
-        // The .crdownload file will have the same name as the final file with .crdownload appended
-        let crdownload_filename = format!("{}.crdownload", expected_filename);
-
-        // Poll for the specific .crdownload file
-        loop {
-            if start.elapsed() > timeout {
-                return Err(anyhow!("Download did not complete within {:?}", timeout));
+        let deadline = Instant::now() + policy.download_timeout;
+        let download = loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if let Some(download) =
+                downloads.wait_for_finish(guid, remaining.min(PROGRESS_INTERVAL))
+            {
+                break download;
             }
+            if remaining.is_zero() {
+                return Err(DownloadError::CompletionTimeout(policy.download_timeout).into());
+            }
+            if let Some(DownloadState::InProgress { received, total }) =
+                downloads.get(guid).map(|download| download.state)
+            {
+                on_progress(received as u64, (total > 0.0).then_some(total as u64));
+            }
+        };
+        if download.state == DownloadState::Canceled {
+            return Err(DownloadError::Canceled(download.filename).into());
+        }
+        tracing::info!("- download complete");
 
-            // Check if the specific .crdownload file exists
-            match std::fs::read_dir(&download_path) {
-                Ok(entries) => {
-                    let has_crdownload = entries
-                        .filter_map(|e| e.ok())
-                        .any(|entry| entry.file_name().to_string_lossy() == crdownload_filename);
-
-                    if !has_crdownload {
-                        // Verify the final file actually exists
-                        let final_path = download_path.join(expected_filename);
-                        if final_path.exists() {
-                            tracing::info!("- download complete");
-                            return Ok(());
-                        }
-                        // If .crdownload is gone but final file doesn't exist yet, keep waiting
-                    }
-                }
-                Err(e) => {
-                    tracing::warn!("Could not read download directory: {}", e);
-                    return Err(anyhow!("Failed to read download directory: {}", e));
+        Ok(download.filename)
+    }
+
+    /// Fetches a download link over HTTP with the browser's cookies.
+    fn fetch_directly(
+        request: &http_download::Request,
+        dest: &Path,
+        mut on_progress: impl FnMut(u64, Option<u64>),
+    ) -> Result<()> {
+        let filename = dest.file_name().unwrap_or_default().to_string_lossy();
+        tracing::info!("- downloading {}...", filename);
+        let mut last_logged = 0;
+        let size = http_download::fetch(request, dest, |received, total| {
+            on_progress(received, total);
+            if let Some(total) = total.filter(|&total| total > 0) {
+                let percent = received * 100 / total;
+                if percent >= last_logged + 10 {
+                    last_logged = percent - percent % 10;
+                    tracing::info!("- {}% ({} of {} bytes)", percent, received, total);
                 }
             }
+        })?;
+        tracing::info!("- download complete ({} bytes)", size);
 
-            // Wait a bit before checking again
-            sleep(Duration::from_millis(500));
-        }
+        Ok(())
     }
 
-    fn get_default_download_dir() -> Result<PathBuf> {
-        // Get the user's home directory
-        let home = dirs::home_dir().ok_or_else(|| anyhow!("Could not determine home directory"))?;
+    /// Records the modal's download link, along with the headers needed to fetch it.
+    fn collect_link(&self, tab: &Tab, track_name: &str) -> Result<TrackLink> {
+        let (filename, request) = self.download_request(tab)?;
+
+        tracing::info!("- link collected for {}", filename);
+        Ok(TrackLink {
+            track: track_name.to_string(),
+            filename,
+            url: request.url,
+            headers: request.headers.into_iter().collect(),
+        })
+    }
 
-        // Default download directory varies by OS
-        #[cfg(target_os = "macos")]
-        let download_dir = home.join("Downloads");
+    /// The filename and HTTP request for the modal's download link, carrying the browser's
+    /// cookies so the site accepts it.
+    fn download_request(&self, tab: &Tab) -> Result<(String, http_download::Request)> {
+        let link = self.extract_download_link(tab)?;
+        let filename = link
+            .path_segments()
+            .and_then(|mut segments| segments.next_back())
+            .filter(|name| !name.is_empty())
+            .ok_or_else(|| DownloadError::InvalidDownloadLink(link.to_string()))?;
+        let filename = urlencoding::decode(filename)
+            .map_err(|e| DownloadError::InvalidDownloadLink(e.to_string()))?
+            .to_string();
 
-        #[cfg(target_os = "linux")]
-        let download_dir = home.join("Downloads");
+        let host = link.host_str().unwrap_or_default();
+        let cookies = tab
+            .get_cookies()?
+            .into_iter()
+            .filter(|cookie| cookie_matches_domain(&cookie.domain, host))
+            .map(|cookie| format!("{}={}", cookie.name, cookie.value))
+            .collect::<Vec<_>>()
+            .join("; ");
+        let user_agent = tab
+            .evaluate("navigator.userAgent", false)?
+            .value
+            .and_then(|v| v.as_str().map(str::to_string))
+            .unwrap_or_default();
+        let request = http_download::Request {
+            url: link.to_string(),
+            headers: vec![
+                ("Cookie".to_string(), cookies),
+                ("User-Agent".to_string(), user_agent),
+                ("Referer".to_string(), tab.get_url()),
+            ],
+        };
 
-        #[cfg(target_os = "windows")]
-        let download_dir = home.join("Downloads");
+        Ok((filename, request))
+    }
 
-        if !download_dir.exists() {
-            return Err(anyhow!(
-                "Default download directory does not exist: {}",
-                download_dir.display()
-            ));
+    fn close_download_modal(&self, tab: &Tab) -> Result<()> {
+        let sel = &self.config.selectors;
+        match tab.find_element(&sel.modal_close_button) {
+            Ok(close_btn) => {
+                close_btn.click()?;
+                // the file is already on its way, so a modal that lingers isn't worth failing
+                // the track over
+                let closed = self.wait_until("the download modal to close", STEP_TIMEOUT, || {
+                    Ok(!tab.is_visible(&sel.download_modal)?)
+                });
+                if let Err(e) = closed {
+                    tracing::warn!("{}, proceeding anyway", e);
+                }
+            }
+            Err(_) => {
+                tracing::warn!("Could not find modal close button, proceeding anyway");
+            }
         }
+        Ok(())
+    }
+
+    /// The absolute url of the download link in the modal.
+    fn extract_download_link(&self, tab: &Tab) -> Result<url::Url> {
+        let href = tab
+            .find(&self.config.selectors.download_link)?
+            .get_attribute_value("href")?
+            .ok_or_else(|| DownloadError::InvalidDownloadLink("missing href".to_string()))?;
+
+        url::Url::parse(&tab.get_url())
+            .and_then(|page| page.join(&href))
+            .map_err(|e| DownloadError::InvalidDownloadLink(format!("{}: {}", href, e)).into())
+    }
 
-        Ok(download_dir)
+    fn download_dir(song: &Song) -> Result<PathBuf> {
+        let dir = match &song.download_path {
+            Some(path) => PathBuf::from(path),
+            None => dirs::download_dir().ok_or_else(|| {
+                DownloadError::DownloadDirectory(
+                    "Could not determine the download directory, pass --download-path".to_string(),
+                )
+            })?,
+        };
+        std::fs::create_dir_all(&dir)?;
+        Ok(dir)
     }
 
-    pub fn extract_track_names(tab: &Tab) -> Result<Vec<String>> {
-        let track_names = tab.find_elements(".mixer .track .track__caption")?;
+    pub fn extract_track_names(&self, tab: &Tab) -> Result<Vec<String>> {
+        let track_names = tab.find_all(&self.config.selectors.track_caption)?;
         let mut names: Vec<String> = vec![];
         for el in track_names {
             // the name may contain other child nodes, so we'll execute a js function
@@ -345,42 +896,40 @@ impl Driver {
         Ok(names)
     }
 
-    fn is_a_song_page(&self, tab: &Tab) -> bool {
-        let has_mixer = tab.find_element("div.mixer").is_ok();
-        let has_download_button = tab.find_element("a.download").is_ok();
+    pub(crate) fn is_a_song_page(&self, tab: &Tab) -> bool {
+        let sel = &self.config.selectors;
+        let has_mixer = tab.find_element(&sel.mixer).is_ok();
+        let has_download_button = tab.find_element(&sel.download_button).is_ok();
 
         has_mixer && has_download_button
     }
 
-    fn is_verify_you_are_human_page(&self, tab: &Tab) -> bool {
+    pub(crate) fn is_verify_you_are_human_page(&self, tab: &Tab) -> bool {
         tab.get_title()
             .ok()
             .unwrap_or_default()
             .contains("Suspicious activity has been detected")
     }
 
-    fn is_downloadable(&self, tab: &Tab) -> bool {
+    pub(crate) fn is_downloadable(&self, tab: &Tab) -> bool {
         // if the download button also has the addtocart class, then this hasn't been purchased
-        let el = tab.find_element("a.download.addtocart").ok();
+        let el = tab
+            .find_element(&self.config.selectors.add_to_cart_button)
+            .ok();
         el.is_none()
     }
 
-    fn adjust_pitch(&self, desired_pitch: i8, tab: &Tab) -> Result<()> {
+    fn adjust_pitch(&self, song_url: &str, desired_pitch: i8, tab: &Tab) -> Result<()> {
         // pitch is remembered per-son on your account, so this logic cannot be deterministic. Instead
         // we''l try to infer the direction we need to go based on what the pitch is currently set to.
-        let pitch_label = tab
-            .find_element("span.pitch__value")
-            .expect("can't find pitch value");
-        let pitch_up_btn = tab
-            .find_element("div.pitch button.btn--pitch[title='Key up' i]")
-            .expect("can't find pitch up button");
-        let pitch_down_btn = tab
-            .find_element("div.pitch button.btn--pitch[title='Key down' i]")
-            .expect("can't find pitch down button");
+        let sel = &self.config.selectors;
+        let pitch_label = tab.find(&sel.pitch_value)?;
+        let pitch_up_btn = tab.find(&sel.pitch_up_button)?;
+        let pitch_down_btn = tab.find(&sel.pitch_down_button)?;
 
         pitch_up_btn.focus()?;
 
-        let current_pitch: i8 = pitch_label.get_inner_text()?.parse()?;
+        let current_pitch = Self::read_pitch(&pitch_label)?;
         let diff = desired_pitch - current_pitch;
         if diff == 0 {
             return Ok(());
@@ -399,19 +948,21 @@ impl Driver {
 
         let mut iterations_allowed = 10;
         loop {
-            assert!(
-                iterations_allowed > 0,
-                "failed to set pitch, breaking to avoid infinite loop"
-            );
+            // bail out rather than looping forever if the label never reaches the target
+            if iterations_allowed == 0 {
+                return Err(DownloadError::PitchNotSet(desired_pitch).into());
+            }
             iterations_allowed -= 1;
 
             tracing::debug!("Pitching tracks...");
-            button.click().expect("couldn't click pitch button");
-            sleep(Duration::from_millis(100));
+            let previous_pitch = Self::read_pitch(&pitch_label)?;
+            button.click()?;
+            self.wait_until("the pitch to change", STEP_TIMEOUT, || {
+                Ok(Self::read_pitch(&pitch_label)? != previous_pitch)
+            })?;
 
-            let new_pitch: i8 = pitch_label.get_inner_text()?.parse()?;
+            let new_pitch = Self::read_pitch(&pitch_label)?;
             tracing::debug!("Pitching is now {}, target: {}", new_pitch, desired_pitch);
-            sleep(Duration::from_millis(100));
 
             if new_pitch == desired_pitch {
                 break;
@@ -420,47 +971,25 @@ impl Driver {
 
         // need to reload the song after pitching
         tracing::info!("Reloading tracks after pitching...");
-        tab.find_element("a#pitch-link")
-            .expect("can't find pitch link")
-            .click()?;
-
-        sleep(Duration::from_secs(4));
+        tab.find(&sel.pitch_reload_link)?.click()?;
+        tab.wait_until_navigated()?;
+        self.wait_until("the tracks to reload", MIXER_TIMEOUT, || {
+            Ok(!tab.is_visible(&sel.pitch_reload_link)? && tab.is_visible(&sel.download_button)?)
+        })?;
+        self.emit(Event::PitchChanged {
+            song: song_url.to_string(),
+            from: current_pitch,
+            to: desired_pitch,
+        });
 
         Ok(())
     }
 
-    #[allow(dead_code)]
-    fn print_source_html(&self, tab: &Tab) {
-        let source_obj = tab
-            .evaluate("document.documentElement.outerHTML", true)
-            .ok()
-            .unwrap();
-        let source_html = source_obj.value.unwrap().as_str().unwrap().to_string();
-        println!("{}", source_html);
-    }
-
-    #[allow(dead_code)]
-    fn record_screencast(&self, tab: &Tab) -> Result<()> {
-        tab.add_event_listener(Arc::new(|event: &Event| match event {
-            Event::PageScreencastFrame(frame_event) => {
-                let bytes = BASE64_STANDARD
-                    .decode(frame_event.params.data.clone())
-                    .unwrap();
-                let ts = frame_event.params.metadata.timestamp.unwrap();
-                std::fs::write(format!("screencast-{}.jpg", ts), &bytes).unwrap();
-            }
-            _ => {}
-        }))?;
-
-        tab.start_screencast(
-            Some(StartScreencastFormatOption::Jpeg),
-            Some(80),
-            Some(1280),
-            Some(720),
-            Some(4),
-        )?;
-
-        Ok(())
+    pub(crate) fn read_pitch(pitch_label: &Element) -> Result<i8> {
+        let text = pitch_label.get_inner_text()?;
+        text.trim()
+            .parse()
+            .map_err(|_| KvError::Browser(anyhow::anyhow!("Unexpected pitch value '{}'", text)))
     }
 }
 
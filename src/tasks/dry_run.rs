@@ -0,0 +1,102 @@
+use crate::download_progress::DownloadProgress;
+use crate::driver::Driver;
+use crate::error::Result;
+use crate::tasks::batch::song_slug;
+use crate::tasks::download_song::{DownloadMode, DownloadOptions};
+use crate::tasks::song_info::SongInfo;
+use serde::Serialize;
+use std::path::PathBuf;
+
+/// What downloading a song would do, worked out without soloing or downloading anything
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DownloadPlan {
+    pub song: SongInfo,
+    pub mode: DownloadMode,
+    /// The tracks that would be generated, in order
+    pub tracks: Vec<String>,
+    /// Tracks a previous run already downloaded, which would be skipped
+    pub skipped: Vec<String>,
+    /// Where the files would be saved, `None` when only their links are collected. The files
+    /// themselves are named by the site as each track is generated.
+    pub download_dir: Option<PathBuf>,
+    /// The key the tracks would be set to first, in half steps from the original
+    pub transpose: i8,
+    pub count_in: bool,
+}
+
+impl DownloadPlan {
+    /// Whether the pitch would be changed before downloading, i.e. it isn't set to
+    /// `transpose` already.
+    pub fn changes_pitch(&self) -> bool {
+        self.song.pitch != Some(self.transpose)
+    }
+}
+
+impl Driver {
+    /// Works out what [`Driver::download_song`] would do for the song at `url`. The driver
+    /// should already be signed in.
+    pub fn plan_download(&self, url: &str, options: &DownloadOptions) -> Result<DownloadPlan> {
+        let dir = self.config.download_path.as_ref().map(PathBuf::from);
+        self.plan(url, options, dir, &self.progress)
+    }
+
+    /// Works out what [`Driver::download_songs`] would do for each of `urls`, in the same
+    /// order. A song that can't be looked at doesn't stop the others.
+    pub fn plan_downloads(
+        &self,
+        urls: &[String],
+        options: &DownloadOptions,
+    ) -> Result<Vec<Result<DownloadPlan>>> {
+        let base_dir = self.batch_dir()?;
+        Ok(urls
+            .iter()
+            .map(|url| {
+                let dir = base_dir.join(song_slug(url));
+                let progress = DownloadProgress::new_with_path(dir.to_str());
+                self.plan(url, options, Some(dir), &progress)
+            })
+            .collect())
+    }
+
+    fn plan(
+        &self,
+        url: &str,
+        options: &DownloadOptions,
+        download_dir: Option<PathBuf>,
+        progress: &DownloadProgress,
+    ) -> Result<DownloadPlan> {
+        let song = self.song_info(url)?;
+
+        let (skipped, download_dir) = match options.mode {
+            // collecting links leaves the progress alone
+            DownloadMode::LinksOnly => (vec![], None),
+            _ => {
+                let done = progress.resumable_tracks(url, options.force_restart)?;
+                let skipped = song
+                    .tracks
+                    .iter()
+                    .filter(|track| done.contains(track))
+                    .cloned()
+                    .collect();
+                // the browser saves to its own default folder when not given one
+                (skipped, download_dir.or_else(dirs::download_dir))
+            }
+        };
+        let tracks = song
+            .tracks
+            .iter()
+            .filter(|track| !skipped.contains(track))
+            .cloned()
+            .collect();
+
+        Ok(DownloadPlan {
+            song,
+            mode: options.mode,
+            tracks,
+            skipped,
+            download_dir,
+            transpose: options.transpose,
+            count_in: options.count_in,
+        })
+    }
+}
@@ -1,2 +1,7 @@
+pub mod batch;
+pub mod doctor;
 pub mod download_song;
+pub mod dry_run;
 pub mod sign_in;
+pub mod song_info;
+mod verification;
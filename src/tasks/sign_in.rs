@@ -1,92 +1,157 @@
 use crate::keystore::Keystore;
-use std::{thread::sleep, time::Duration};
+use crate::session::Session;
+use headless_chrome::protocol::cdp::Page;
+use headless_chrome::Tab;
+use std::collections::BTreeMap;
+use std::time::Duration;
 
-use crate::driver::Driver;
-use anyhow::Result;
+use crate::driver::{Driver, TabExt};
+use crate::error::{KvError, Result};
+use crate::events::Event;
 
-impl Driver {
-    pub fn sign_in(&self, user: &str, pass: &str) -> Result<()> {
-        let tab = self.browser.new_tab()?;
+/// How long to wait for the login form to show up
+const FORM_TIMEOUT: Duration = Duration::from_secs(20);
 
-        // navigate to the homepage
-        tab.navigate_to(&format!("https://{}", self.config.domain))?
-            .wait_until_navigated()?;
-
-        // this doesn't seem to work yet...
-        if let Ok(cookie) = Keystore::get_auth_cookie() {
-            tracing::debug!("Cookies before:");
-            for c in tab.get_cookies()? {
-                tracing::debug!(cookie = format!("{}: {}", c.name, c.value), "🍪");
-            }
+/// How long the account menu may take to show up after submitting the login form
+const SIGNED_IN_TIMEOUT: Duration = Duration::from_secs(15);
 
-            tracing::info!("Using previous cookie value");
-            tracing::debug!(cookie = serde_json::to_string(&cookie).unwrap());
+impl Driver {
+    pub fn sign_in(&self, user: &str, pass: &str) -> Result<()> {
+        let tab = self.new_tab()?;
 
-            // only set it if it's an authenticated session with a user id?
-            if cookie.value.contains("|u-i:") {
-                tracing::debug!("Setting cookie");
-                tab.set_cookies(vec![cookie])
-                    .expect("unable to set cookies");
-                tracing::debug!("Reloading tab...");
-                tab.reload(true, None)?;
+        self.sign_in_on_tab(&tab, user, pass)
+            .map_err(|e| self.with_diagnostics(&tab, "sign-in", e))?;
+        self.emit(Event::SignedIn {
+            user: user.to_string(),
+        });
+        Ok(())
+    }
 
-                sleep(Duration::from_secs(2));
+    fn sign_in_on_tab(&self, tab: &Tab, user: &str, pass: &str) -> Result<()> {
+        // restore the previous session before the first navigation so the site sees us as signed in.
+        // an attached browser brings its own profile, so leave its cookies alone.
+        let restored = if self.config.connect.is_none() && self.config.persist_session {
+            self.restore_session(tab)?
+        } else {
+            None
+        };
 
-                tracing::debug!("Cookies after:");
-                for c in tab.get_cookies()? {
-                    tracing::debug!(cookie = format!("{}: {}", c.name, c.value), "🍪");
-                }
-            }
+        // navigate to the homepage
+        tab.navigate(&format!("https://{}", self.config.domain))?;
 
-            // continue to check for login link in case this cookie isn't valid anymore
+        if let Some(script_id) = restored {
+            tab.call_method(Page::RemoveScriptToEvaluateOnNewDocument {
+                identifier: script_id,
+            })?;
         }
 
-        tracing::info!(user = user, "Logging in user");
-
-        let login_link = tab
-            .find_element(".navigation a[href='/my/login.html']")
-            .ok();
-
-        // if we don't have a login link, we're already signed in (from a cookie)
-        if login_link.is_none() {
+        if self.is_signed_in(tab) {
             tracing::info!(user = user, "Already signed in");
             return Ok(());
         }
 
+        tracing::info!(user = user, "Logging in user");
+
+        let sel = &self.config.selectors;
+        let login_link = tab.find(&sel.login_link)?;
+
         // visit login page
-        login_link.unwrap().click()?;
+        login_link.click()?;
 
         // fill out form
-        tab.wait_for_element("#frm_login")
-            .expect("couldn't find username input")
+        tab.wait_for(&sel.login_form_username, FORM_TIMEOUT)?
             .focus()?;
-        self.type_fast(&tab, user);
+        self.type_fast(tab, user)?;
 
-        tab.wait_for_element("#frm_password")
-            .expect("couldn't find password input")
+        tab.wait_for(&sel.login_form_password, FORM_TIMEOUT)?
             .focus()?;
-        self.type_fast(&tab, pass);
+        self.type_fast(tab, pass)?;
 
         // submit
-        tab.find_element("#sbm")
-            .expect("couldn't find submit button")
-            .click()?;
+        tab.find(&sel.login_form_submit)?.click()?;
 
         tab.wait_until_navigated()?;
 
-        sleep(Duration::from_secs(2));
-
-        // save cookie for next time
-        let cookies = tab.get_cookies()?;
-        if let Some(session_cookie) = cookies.iter().find(|c| c.name == "karaoke-version") {
-            tracing::info!("Saving session cookie for next time");
-            tracing::debug!(
-                cookie = format!("{}: {}", session_cookie.name, session_cookie.value),
-                "🍪"
-            );
-            Keystore::set_auth_cookie(session_cookie)?;
+        let signed_in = self.wait_until("the account menu", SIGNED_IN_TIMEOUT, || {
+            Ok(self.is_signed_in(tab))
+        });
+        if signed_in.is_err() {
+            return Err(KvError::Auth(
+                "Sign in failed. Check your username and password.".to_string(),
+            ));
+        }
+
+        // save the session for next time. Signing in worked all the same if that fails, i.e.
+        // where there is no keychain to save it in
+        if self.config.persist_session {
+            tracing::info!("Saving session for next time");
+            if let Err(e) = self.save_session(tab) {
+                tracing::warn!("Unable to save the session for next time: {}", e);
+            }
         }
 
         Ok(())
     }
+
+    /// Loads the saved session (if any) into the browser. Cookies are set directly, while
+    /// local storage is injected by a script that runs on the next document load. Returns the
+    /// identifier of that script so it can be removed once the page has loaded.
+    fn restore_session(&self, tab: &Tab) -> Result<Option<Page::ScriptIdentifier>> {
+        let session = match Keystore::get_session() {
+            Ok(session) => session.without_expired(),
+            Err(e) => {
+                tracing::debug!("No previous session: {}", e);
+                return Ok(None);
+            }
+        };
+
+        if session.is_empty() {
+            tracing::debug!("Previous session has expired");
+            return Ok(None);
+        }
+
+        tracing::info!("Using previous session");
+        for c in &session.cookies {
+            tracing::debug!(cookie = format!("{}: {}", c.name, c.value), "🍪");
+        }
+        tab.set_cookies(session.cookie_params())?;
+
+        match session.local_storage_script(&self.config.domain) {
+            Some(source) => {
+                let script = tab.call_method(Page::AddScriptToEvaluateOnNewDocument {
+                    source,
+                    world_name: None,
+                    include_command_line_api: None,
+                })?;
+                Ok(Some(script.identifier))
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn save_session(&self, tab: &Tab) -> Result<()> {
+        let local_storage: BTreeMap<String, String> = tab
+            .evaluate("JSON.stringify(Object.assign({}, localStorage))", false)?
+            .value
+            .and_then(|v| v.as_str().and_then(|s| serde_json::from_str(s).ok()))
+            .unwrap_or_default();
+
+        let session =
+            Session::from_browser(&tab.get_cookies()?, local_storage, &self.config.domain);
+        for c in &session.cookies {
+            tracing::debug!(cookie = format!("{}: {}", c.name, c.value), "🍪");
+        }
+        Keystore::set_session(&session)
+    }
+
+    /// Checks the page content for signs of a signed-in account, rather than relying only on
+    /// the absence of the login link.
+    pub fn is_signed_in(&self, tab: &Tab) -> bool {
+        let sel = &self.config.selectors;
+        let has_login_link = tab.find_element(&sel.login_link).is_ok();
+        let has_logout_link = tab.find_element(&sel.logout_link).is_ok();
+        let has_account_link = tab.find_element(&sel.account_link).is_ok();
+
+        !has_login_link && has_logout_link && has_account_link
+    }
 }
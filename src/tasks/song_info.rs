@@ -0,0 +1,50 @@
+use crate::driver::{Driver, TabExt};
+use crate::error::Result;
+use crate::tasks::download_song::DownloadError;
+use serde::Serialize;
+
+/// What a song page tells us before anything is downloaded
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SongInfo {
+    pub url: String,
+    /// The page title
+    pub title: Option<String>,
+    /// The name of every track in the mixer, in order
+    pub tracks: Vec<String>,
+    /// Whether the account has bought the song, and so can download it
+    pub purchased: bool,
+    /// The key the tracks are currently set to, in half steps from the original
+    pub pitch: Option<i8>,
+}
+
+impl Driver {
+    /// Loads the song page at `url` and reads what's on it, without changing anything. The
+    /// driver should already be signed in.
+    pub fn song_info(&self, url: &str) -> Result<SongInfo> {
+        let tab = self.new_tab()?;
+        tab.navigate(url)?;
+
+        if !self.is_a_song_page(&tab) {
+            if self.is_verify_you_are_human_page(&tab) {
+                self.complete_human_verification(&tab, url)?;
+            } else {
+                return Err(DownloadError::NotASongPage.into());
+            }
+        }
+
+        let pitch = match tab.find(&self.config.selectors.pitch_value) {
+            Ok(label) => Self::read_pitch(&label).ok(),
+            Err(_) => None,
+        };
+        let info = SongInfo {
+            url: url.to_string(),
+            title: tab.get_title().ok(),
+            tracks: self.extract_track_names(&tab)?,
+            purchased: self.is_downloadable(&tab),
+            pitch,
+        };
+
+        let _ = tab.close(false);
+        Ok(info)
+    }
+}
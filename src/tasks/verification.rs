@@ -0,0 +1,86 @@
+use crate::driver::{BrowserOptions, Driver, TabExt};
+use crate::error::{KvError, Result};
+use crate::session::SessionCookie;
+use crate::tasks::download_song::DownloadError;
+use headless_chrome::protocol::cdp::Network::CookieParam;
+use headless_chrome::Tab;
+use std::time::Duration;
+
+/// How long the user has to complete the check
+const VERIFICATION_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+
+impl Driver {
+    /// Hands a "verify you are human" check on `tab` over to the user, and returns once `tab`
+    /// shows the song at `url` again. Fails straight away when not running interactively.
+    pub(crate) fn complete_human_verification(&self, tab: &Tab, url: &str) -> Result<()> {
+        if !self.config.interactive {
+            return Err(DownloadError::HumanVerificationRequired.into());
+        }
+
+        if self.config.headless && self.config.connect.is_none() {
+            self.verify_in_visible_browser(tab, url)?;
+            tab.navigate(url)?;
+        } else {
+            tracing::warn!(
+                "The site wants to verify you are human. Complete the check in the browser window to continue..."
+            );
+            self.wait_for_song_page(tab)?;
+        }
+
+        if !self.is_a_song_page(tab) {
+            return Err(DownloadError::HumanVerificationRequired.into());
+        }
+        Ok(())
+    }
+
+    /// Opens a visible browser with the same cookies for the user to complete the check in, then
+    /// carries the resulting cookies back to `tab`.
+    fn verify_in_visible_browser(&self, tab: &Tab, url: &str) -> Result<()> {
+        tracing::warn!(
+            "The site wants to verify you are human. Opening a browser window, complete the check there to continue..."
+        );
+
+        // a second browser can't share the profile directory with the first
+        let options = BrowserOptions {
+            user_data_dir: None,
+            ..self.config.browser.clone()
+        };
+        // i.e. there is no display to show it on
+        let browser = Driver::launch(&options, false).map_err(|e| {
+            tracing::warn!("Unable to open a browser window for the check: {}", e);
+            KvError::from(DownloadError::HumanVerificationRequired)
+        })?;
+        let visible = browser.new_tab()?;
+        visible.set_cookies(cookie_params(tab)?)?;
+        visible.navigate(url)?;
+
+        self.wait_for_song_page(&visible)?;
+
+        // the clearance is usually tied to the user agent it was earned with, so take both
+        tab.set_cookies(cookie_params(&visible)?)?;
+        let user_agent = visible
+            .evaluate("navigator.userAgent", false)?
+            .value
+            .and_then(|v| v.as_str().map(str::to_string));
+        if let Some(user_agent) = user_agent {
+            tab.set_user_agent(&user_agent, None, None)?;
+        }
+
+        tracing::info!("Verification complete, carrying on in the background");
+        Ok(())
+    }
+
+    fn wait_for_song_page(&self, tab: &Tab) -> Result<()> {
+        self.wait_until("the song page", VERIFICATION_TIMEOUT, || {
+            Ok(self.is_a_song_page(tab))
+        })
+    }
+}
+
+fn cookie_params(tab: &Tab) -> Result<Vec<CookieParam>> {
+    Ok(tab
+        .get_cookies()?
+        .iter()
+        .map(|cookie| SessionCookie::from(cookie).to_param())
+        .collect())
+}
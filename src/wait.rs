@@ -0,0 +1,21 @@
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+/// Checks `condition` every `interval` until it holds or `timeout` has passed. Returns whether
+/// it held, or the first error the check ran into.
+pub fn poll<E>(
+    timeout: Duration,
+    interval: Duration,
+    mut condition: impl FnMut() -> Result<bool, E>,
+) -> Result<bool, E> {
+    let start = Instant::now();
+    loop {
+        if condition()? {
+            return Ok(true);
+        }
+        if start.elapsed() >= timeout {
+            return Ok(false);
+        }
+        sleep(interval.min(timeout.saturating_sub(start.elapsed())));
+    }
+}
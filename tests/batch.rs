@@ -0,0 +1,16 @@
+use kv_downloader::tasks::batch::song_slug;
+
+#[test]
+fn names_song_directories_after_the_url() {
+    assert_eq!(
+        song_slug("https://www.karaoke-version.com/custombackingtrack/the-smashing-pumpkins/cherub-rock.html"),
+        "cherub-rock"
+    );
+    assert_eq!(
+        song_slug(
+            "https://www.karaoke-version.com/custombackingtrack/queen/don't-stop-me-now.html/"
+        ),
+        "don-t-stop-me-now"
+    );
+    assert_eq!(song_slug("https://www.karaoke-version.com/"), "song");
+}
@@ -0,0 +1,9 @@
+use kv_downloader::error::KvError;
+use kv_downloader::KvClient;
+
+#[test]
+fn requires_credentials() {
+    let result = KvClient::builder().download_dir("/music").build();
+
+    assert!(matches!(result, Err(KvError::Auth(_))));
+}
@@ -0,0 +1,60 @@
+use kv_downloader::config_file::ConfigFile;
+use std::path::PathBuf;
+
+#[test]
+fn parses_browser_section() {
+    let config = ConfigFile::parse(
+        r#"
+        [browser]
+        executable = "/usr/bin/chromium"
+        proxy = "socks5://127.0.0.1:1080"
+        sandbox = false
+        args = ["--lang=en-US"]
+        window_size = [1280, 800]
+        "#,
+    )
+    .unwrap();
+
+    assert_eq!(
+        config.browser.executable,
+        Some(PathBuf::from("/usr/bin/chromium"))
+    );
+    assert_eq!(
+        config.browser.proxy.as_deref(),
+        Some("socks5://127.0.0.1:1080")
+    );
+    assert_eq!(config.browser.sandbox, Some(false));
+    assert_eq!(config.browser.args, vec!["--lang=en-US".to_string()]);
+    assert_eq!(config.browser.window_size, Some((1280, 800)));
+    assert_eq!(config.browser.user_data_dir, None);
+}
+
+#[test]
+fn rejects_unknown_keys() {
+    assert!(ConfigFile::parse("[browser]\nexecutabel = \"/usr/bin/chromium\"").is_err());
+}
+
+#[test]
+fn parses_retry_section() {
+    let config = ConfigFile::parse(
+        r#"
+        [retry]
+        max_attempts = 5
+        backoff = 10
+        jitter = 0.1
+        "#,
+    )
+    .unwrap();
+
+    assert_eq!(config.retry.max_attempts, Some(5));
+    assert_eq!(config.retry.backoff, Some(10));
+    assert_eq!(config.retry.jitter, Some(0.1));
+    assert_eq!(config.retry.modal_timeout, None);
+}
+
+#[test]
+fn rejects_a_cap_of_zero() {
+    assert!(ConfigFile::parse("[limits]\nper_hour = 0").is_err());
+    assert!(ConfigFile::parse("[limits]\nper_day = 0").is_err());
+    assert!(ConfigFile::parse("[limits]\nper_day = 1").is_ok());
+}
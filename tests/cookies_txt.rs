@@ -0,0 +1,40 @@
+use kv_downloader::cookies_txt;
+
+const COOKIES_TXT: &str = "# Netscape HTTP Cookie File
+# This is a generated file! Do not edit.
+
+#HttpOnly_.karaoke-version.com\tTRUE\t/\tTRUE\t1893456000\tkaraoke-version\tabc|u-i:123
+www.karaoke-version.com\tFALSE\t/\tFALSE\t0\tlang\ten
+";
+
+#[test]
+fn parses_cookies_txt() {
+    let cookies = cookies_txt::parse(COOKIES_TXT).unwrap();
+
+    assert_eq!(cookies.len(), 2);
+
+    assert_eq!(cookies[0].name, "karaoke-version");
+    assert_eq!(cookies[0].value, "abc|u-i:123");
+    assert_eq!(cookies[0].domain, ".karaoke-version.com");
+    assert_eq!(cookies[0].expires, 1893456000.0);
+    assert!(cookies[0].http_only);
+    assert!(cookies[0].secure);
+
+    assert_eq!(cookies[1].name, "lang");
+    assert_eq!(cookies[1].expires, -1.0);
+    assert!(!cookies[1].http_only);
+    assert!(!cookies[1].secure);
+}
+
+#[test]
+fn round_trips_cookies_txt() {
+    let cookies = cookies_txt::parse(COOKIES_TXT).unwrap();
+    let exported = cookies_txt::format(&cookies);
+
+    assert_eq!(cookies_txt::parse(&exported).unwrap(), cookies);
+}
+
+#[test]
+fn rejects_malformed_lines() {
+    assert!(cookies_txt::parse("www.karaoke-version.com\tFALSE\t/\n").is_err());
+}
@@ -0,0 +1,69 @@
+use std::fs;
+use std::path::PathBuf;
+
+use kv_downloader::download_progress::DownloadProgress;
+
+fn progress(name: &str) -> (PathBuf, DownloadProgress) {
+    let dir = std::env::temp_dir().join(format!("kv-progress-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    let progress = DownloadProgress::new_with_path(dir.to_str());
+    (dir, progress)
+}
+
+#[test]
+fn resumes_the_same_song() {
+    let (_dir, progress) = progress("resume");
+    progress.start("https://example.com/a.html", false).unwrap();
+    progress.mark_track_downloaded("Bass").unwrap();
+
+    progress.start("https://example.com/a.html", false).unwrap();
+
+    assert!(progress.is_track_downloaded("Bass").unwrap());
+}
+
+#[test]
+fn starts_over_for_another_song() {
+    let (_dir, progress) = progress("other");
+    progress.start("https://example.com/a.html", false).unwrap();
+    progress.mark_track_downloaded("Bass").unwrap();
+
+    progress.start("https://example.com/b.html", false).unwrap();
+
+    assert!(!progress.is_track_downloaded("Bass").unwrap());
+    assert!(progress.is_same_url("https://example.com/b.html").unwrap());
+}
+
+#[test]
+fn starts_over_when_forced() {
+    let (_dir, progress) = progress("force");
+    progress.start("https://example.com/a.html", false).unwrap();
+    progress.mark_track_downloaded("Bass").unwrap();
+
+    progress.start("https://example.com/a.html", true).unwrap();
+
+    assert!(!progress.is_track_downloaded("Bass").unwrap());
+}
+
+#[test]
+fn reports_resumable_tracks_without_changing_anything() {
+    let (_dir, progress) = progress("resumable");
+    progress.start("https://example.com/a.html", false).unwrap();
+    progress.mark_track_downloaded("Bass").unwrap();
+
+    assert_eq!(
+        progress
+            .resumable_tracks("https://example.com/a.html", false)
+            .unwrap(),
+        vec!["Bass".to_string()]
+    );
+    assert!(progress
+        .resumable_tracks("https://example.com/a.html", true)
+        .unwrap()
+        .is_empty());
+    assert!(progress
+        .resumable_tracks("https://example.com/b.html", false)
+        .unwrap()
+        .is_empty());
+    assert!(progress.is_track_downloaded("Bass").unwrap());
+}
@@ -15,14 +15,14 @@ fn extracts_track_names() -> Result<(), Box<dyn Error>> {
     let driver = Driver::new(Config {
         headless: true,
         ..Default::default()
-    });
+    })?;
 
     let tab = driver.browser.new_tab().unwrap();
     let file_server = Server::with_dumb_html(include_str!("./fixtures/cherub-rock.html"));
     tab.navigate_to(&file_server.url())?;
     tab.wait_until_navigated()?;
 
-    let names = Driver::extract_track_names(&tab)?;
+    let names = driver.extract_track_names(&tab)?;
 
     assert_eq!(
         names,
@@ -41,3 +41,31 @@ fn extracts_track_names() -> Result<(), Box<dyn Error>> {
 
     Ok(())
 }
+
+#[test]
+fn reads_the_mixer_state() -> Result<(), Box<dyn Error>> {
+    let driver = Driver::new(Config {
+        headless: true,
+        ..Default::default()
+    })?;
+
+    let tab = driver.browser.new_tab().unwrap();
+    let file_server = Server::with_dumb_html(include_str!("./fixtures/cherub-rock.html"));
+    tab.navigate_to(&file_server.url())?;
+    tab.wait_until_navigated()?;
+
+    let state = driver.mixer_state(&tab)?;
+    assert_eq!(state.soloed.len(), 9);
+    assert!(state.is_reset());
+
+    // what the page does when a track is soloed
+    tab.evaluate(
+        "document.querySelectorAll('.track__controls.track__solo')[2].classList.add('is-active')",
+        false,
+    )?;
+    let state = driver.mixer_state(&tab)?;
+    assert!(state.is_solo(2));
+    assert_eq!(state.audible(), vec![2]);
+
+    Ok(())
+}
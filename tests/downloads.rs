@@ -0,0 +1,77 @@
+use kv_downloader::downloads::{DownloadState, DownloadTracker};
+use url::Url;
+
+#[test]
+fn tracks_downloads_by_guid() {
+    let mut tracker = DownloadTracker::default();
+    assert_eq!(tracker.started(), 0);
+
+    tracker.begin("a", "https://example.com/drums.mp3", "drums.mp3");
+    tracker.begin("b", "https://example.com/bass.mp3", "bass.mp3");
+    assert_eq!(tracker.started(), 2);
+    assert_eq!(tracker.nth(1).unwrap().filename, "bass.mp3");
+
+    tracker.update(
+        "b",
+        DownloadState::InProgress {
+            received: 10.0,
+            total: 20.0,
+        },
+    );
+    tracker.update("a", DownloadState::Completed);
+    tracker.update("b", DownloadState::Canceled);
+
+    assert_eq!(tracker.get("a").unwrap().state, DownloadState::Completed);
+    assert_eq!(tracker.get("b").unwrap().state, DownloadState::Canceled);
+}
+
+#[test]
+fn ignores_progress_for_unknown_downloads() {
+    let mut tracker = DownloadTracker::default();
+    tracker.update("missing", DownloadState::Completed);
+
+    assert_eq!(tracker.started(), 0);
+    assert!(tracker.get("missing").is_none());
+}
+
+#[test]
+fn matches_downloads_to_the_modal_link() {
+    let mut tracker = DownloadTracker::default();
+    // a late download from an attempt that timed out, then this track's
+    tracker.begin(
+        "a",
+        "https://example.com/files/Song_Drums.mp3",
+        "Song_Drums.mp3",
+    );
+    tracker.begin(
+        "b",
+        "https://example.com/files/Song_Bass.mp3",
+        "Song_Bass.mp3",
+    );
+
+    let link = Url::parse("https://example.com/files/Song_Bass.mp3").unwrap();
+    assert_eq!(tracker.find_from(0, Some(&link), "Bass").unwrap().guid, "b");
+    assert!(tracker.find_from(2, Some(&link), "Bass").is_none());
+
+    // the browser may name the file after the link even when the url differs
+    let link = Url::parse("https://cdn.example.com/files/Song_Drums.mp3?token=1").unwrap();
+    assert_eq!(
+        tracker.find_from(0, Some(&link), "Drums").unwrap().guid,
+        "a"
+    );
+
+    let link = Url::parse("https://example.com/files/Song_Guitar.mp3").unwrap();
+    assert!(tracker.find_from(0, Some(&link), "Guitar").is_none());
+}
+
+#[test]
+fn matches_downloads_to_the_track_name_without_a_link() {
+    let mut tracker = DownloadTracker::default();
+    tracker.begin("a", "https://example.com/1", "Song_Lead_Vocal.mp3");
+    tracker.begin("b", "https://example.com/2", "Song_Bass_Guitar.mp3");
+
+    assert_eq!(tracker.find_from(0, None, "Bass Guitar").unwrap().guid, "b");
+    assert_eq!(tracker.find_from(0, None, "Lead vocal").unwrap().guid, "a");
+    assert!(tracker.find_from(0, None, "Drums").is_none());
+    assert!(tracker.find_from(0, None, "").is_none());
+}
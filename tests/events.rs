@@ -0,0 +1,82 @@
+use std::io::{self, Write};
+use std::sync::{Arc, Mutex};
+
+use kv_downloader::events::{Event, EventStream};
+use kv_downloader::observer::Observer;
+use kv_downloader::tasks::download_song::{DownloadReport, DownloadedTrack};
+
+#[derive(Clone, Default)]
+struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+impl Write for Buffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn writes_one_json_event_per_line() {
+    let buffer = Buffer::default();
+    let events = EventStream::new(Box::new(buffer.clone()));
+
+    events.on_event(&Event::SignedIn {
+        user: "me@example.com".to_string(),
+    });
+    events.on_event(&Event::TrackCompleted {
+        song: "https://www.karaoke-version.com/song".to_string(),
+        track: "Bass".to_string(),
+        path: Some("/music/Song(Bass).mp3".into()),
+    });
+
+    let output = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+    let lines: Vec<serde_json::Value> = output
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(lines.len(), 2);
+    assert_eq!(lines[0]["event"], "signed_in");
+    assert_eq!(lines[0]["user"], "me@example.com");
+    assert_eq!(lines[1]["event"], "track_completed");
+    assert_eq!(lines[1]["track"], "Bass");
+    assert_eq!(lines[1]["path"], "/music/Song(Bass).mp3");
+}
+
+#[test]
+fn reports_failures_with_whether_they_are_retried() {
+    let value = serde_json::to_value(Event::TrackFailed {
+        song: "https://www.karaoke-version.com/song".to_string(),
+        track: "Bass".to_string(),
+        attempt: 1,
+        error: "Timed out".to_string(),
+        retrying: true,
+    })
+    .unwrap();
+
+    assert_eq!(value["event"], "track_failed");
+    assert_eq!(value["attempt"], 1);
+    assert_eq!(value["retrying"], true);
+}
+
+#[test]
+fn counts_only_this_runs_tracks_as_completed_on_a_resumed_song() {
+    let report = DownloadReport {
+        song: "https://www.karaoke-version.com/song".to_string(),
+        downloaded: vec![DownloadedTrack {
+            track: "Drums".to_string(),
+            path: None,
+        }],
+        skipped: vec!["Bass".to_string(), "Guitar".to_string()],
+        failed: vec!["Vocals".to_string()],
+        ..Default::default()
+    };
+
+    let value = serde_json::to_value(Event::song_completed(&report)).unwrap();
+    assert_eq!(value["event"], "song_completed");
+    assert_eq!(value["completed"], 1);
+    assert_eq!(value["skipped"], 2);
+    assert_eq!(value["failed"], serde_json::json!(["Vocals"]));
+}
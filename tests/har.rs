@@ -0,0 +1,93 @@
+use kv_downloader::har::{self, HarEntry};
+
+fn login_entry() -> HarEntry {
+    HarEntry {
+        started: 1700000000.0,
+        start_timestamp: 100.0,
+        response_timestamp: Some(100.25),
+        end_timestamp: Some(100.5),
+        method: "POST".to_string(),
+        url: "https://www.karaoke-version.com/my/login.html?back=%2Fmy%2Findex.html".to_string(),
+        request_headers: vec![
+            (
+                "Content-Type".to_string(),
+                "application/x-www-form-urlencoded".to_string(),
+            ),
+            ("Cookie".to_string(), "karaoke-version=abc".to_string()),
+        ],
+        post_data: Some("frm_login=user%40example.com&frm_password=hunter2".to_string()),
+        status: Some(302),
+        status_text: "Found".to_string(),
+        http_version: "h2".to_string(),
+        response_headers: vec![
+            (
+                "set-cookie".to_string(),
+                "karaoke-version=def; path=/".to_string(),
+            ),
+            ("location".to_string(), "/my/index.html".to_string()),
+        ],
+        mime_type: "text/html".to_string(),
+        body_size: 512.0,
+        redirect_url: "https://www.karaoke-version.com/my/index.html".to_string(),
+        error: None,
+    }
+}
+
+#[test]
+fn redacts_cookies_and_auth_headers() {
+    assert_eq!(har::redact_header("Cookie", "a=b"), "[redacted]");
+    assert_eq!(har::redact_header("set-cookie", "a=b"), "[redacted]");
+    assert_eq!(
+        har::redact_header("Authorization", "Bearer x"),
+        "[redacted]"
+    );
+    assert_eq!(har::redact_header("Accept", "text/html"), "text/html");
+}
+
+#[test]
+fn redacts_passwords_in_post_data() {
+    assert_eq!(
+        har::redact_post_data("frm_login=me&frm_password=hunter2&sbm=Login"),
+        "frm_login=me&frm_password=[redacted]&sbm=Login"
+    );
+    assert_eq!(
+        har::redact_post_data("{\"password\": \"hunter2\"}"),
+        "[redacted]"
+    );
+    assert_eq!(har::redact_post_data("{\"song\": 1}"), "{\"song\": 1}");
+}
+
+#[test]
+fn builds_a_redacted_har() {
+    let har = har::to_har(&[login_entry()]);
+    let log = &har["log"];
+    assert_eq!(log["version"], "1.2");
+
+    let entry = &log["entries"][0];
+    assert_eq!(entry["startedDateTime"], "2023-11-14T22:13:20.000Z");
+    assert_eq!(entry["time"], 500.0);
+    assert_eq!(entry["timings"]["wait"], 250.0);
+
+    let request = &entry["request"];
+    assert_eq!(request["method"], "POST");
+    assert_eq!(request["queryString"][0]["value"], "/my/index.html");
+    assert_eq!(request["headers"][1]["value"], "[redacted]");
+    assert_eq!(
+        request["postData"]["text"],
+        "frm_login=user%40example.com&frm_password=[redacted]"
+    );
+
+    let response = &entry["response"];
+    assert_eq!(response["status"], 302);
+    assert_eq!(response["headers"][0]["value"], "[redacted]");
+    assert_eq!(response["headers"][1]["value"], "/my/index.html");
+    assert_eq!(
+        response["redirectURL"],
+        "https://www.karaoke-version.com/my/index.html"
+    );
+
+    let text = har.to_string();
+    assert!(!text.contains("hunter2"));
+    assert!(!text.contains("karaoke-version=abc"));
+    assert!(!text.contains("karaoke-version=def"));
+}
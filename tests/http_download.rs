@@ -0,0 +1,234 @@
+mod server;
+
+use std::fs;
+use std::io::Cursor;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use server::Server;
+
+use kv_downloader::error::KvError;
+use kv_downloader::http_download::{self, Partial, Request};
+use kv_downloader::tasks::download_song::DownloadError;
+
+const BODY: &[u8] = b"0123456789abcdefghijklmnopqrstuvwxyz";
+const ETAG: &str = "\"v1\"";
+
+type Headers = Vec<(String, String)>;
+
+fn temp_dir(name: &str) -> PathBuf {
+    let dir =
+        std::env::temp_dir().join(format!("kv-http-download-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Serves `BODY`, honouring `Range` requests, and remembers the headers of each request
+fn range_server(requests: Arc<Mutex<Vec<Headers>>>) -> Server {
+    versioned_server(requests, BODY, ETAG)
+}
+
+/// Serves `body` tagged `etag`, honouring `Range` requests unless their `If-Range` is for
+/// another version
+fn versioned_server(
+    requests: Arc<Mutex<Vec<Headers>>>,
+    body: &'static [u8],
+    etag: &'static str,
+) -> Server {
+    Server::new(move |request: tiny_http::Request| {
+        let headers: Headers = request
+            .headers()
+            .iter()
+            .map(|h| (h.field.to_string(), h.value.to_string()))
+            .collect();
+        let range_start = headers
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case("range"))
+            .and_then(|(_, value)| value.strip_prefix("bytes="))
+            .and_then(|value| value.trim_end_matches('-').parse::<usize>().ok());
+        let same_version = headers
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case("if-range"))
+            .is_none_or(|(_, value)| value == etag);
+        requests.lock().unwrap().push(headers);
+
+        let etag_header = tiny_http::Header::from_bytes(&b"ETag"[..], etag.as_bytes()).unwrap();
+        let response = match range_start.filter(|_| same_version) {
+            Some(start) => {
+                let content_range = format!("bytes {}-{}/{}", start, body.len() - 1, body.len());
+                tiny_http::Response::new(
+                    206.into(),
+                    vec![
+                        etag_header,
+                        tiny_http::Header::from_bytes(
+                            &b"Content-Range"[..],
+                            content_range.as_bytes(),
+                        )
+                        .unwrap(),
+                    ],
+                    Cursor::new(&body[start..]),
+                    Some(body.len() - start),
+                    None,
+                )
+            }
+            None => tiny_http::Response::new(
+                200.into(),
+                vec![etag_header],
+                Cursor::new(body),
+                Some(body.len()),
+                None,
+            ),
+        };
+        request.respond(response)
+    })
+}
+
+#[test]
+fn downloads_to_the_final_path() {
+    let requests = Arc::new(Mutex::new(vec![]));
+    let server = range_server(requests.clone());
+    let dest = temp_dir("full").join("track.mp3");
+
+    let mut progress = vec![];
+    let size = http_download::fetch(
+        &Request {
+            url: format!("{}/track.mp3", server.url()),
+            headers: vec![("Cookie".to_string(), "session=abc".to_string())],
+        },
+        &dest,
+        |received, total| progress.push((received, total)),
+    )
+    .unwrap();
+
+    assert_eq!(size, BODY.len() as u64);
+    assert_eq!(fs::read(&dest).unwrap(), BODY);
+    assert!(!http_download::part_path(&dest).exists());
+    assert!(!Partial::path(&dest).exists());
+    assert_eq!(
+        progress.last(),
+        Some(&(BODY.len() as u64, Some(BODY.len() as u64)))
+    );
+
+    let requests = requests.lock().unwrap();
+    assert!(requests[0]
+        .iter()
+        .any(|(name, value)| name == "Cookie" && value == "session=abc"));
+}
+
+#[test]
+fn resumes_a_partial_download() {
+    let requests = Arc::new(Mutex::new(vec![]));
+    let server = range_server(requests.clone());
+    let dest = temp_dir("resume").join("track.mp3");
+    let url = format!("{}/track.mp3", server.url());
+    fs::write(http_download::part_path(&dest), &BODY[..10]).unwrap();
+    Partial {
+        url: url.clone(),
+        validator: Some(ETAG.to_string()),
+    }
+    .save(&dest)
+    .unwrap();
+
+    let size = http_download::fetch(
+        &Request {
+            url,
+            headers: vec![],
+        },
+        &dest,
+        |_, _| {},
+    )
+    .unwrap();
+
+    assert_eq!(size, BODY.len() as u64);
+    assert_eq!(fs::read(&dest).unwrap(), BODY);
+
+    let requests = requests.lock().unwrap();
+    assert!(requests[0]
+        .iter()
+        .any(|(name, value)| name == "Range" && value == "bytes=10-"));
+    assert!(requests[0]
+        .iter()
+        .any(|(name, value)| name == "If-Range" && value == ETAG));
+}
+
+#[test]
+fn starts_over_when_the_file_changed() {
+    const NEW_BODY: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+    let requests = Arc::new(Mutex::new(vec![]));
+    let server = versioned_server(requests.clone(), NEW_BODY, "\"v2\"");
+    let dest = temp_dir("changed").join("track.mp3");
+    let url = format!("{}/track.mp3", server.url());
+    fs::write(http_download::part_path(&dest), &BODY[..10]).unwrap();
+    Partial {
+        url: url.clone(),
+        validator: Some(ETAG.to_string()),
+    }
+    .save(&dest)
+    .unwrap();
+
+    let size = http_download::fetch(
+        &Request {
+            url,
+            headers: vec![],
+        },
+        &dest,
+        |_, _| {},
+    )
+    .unwrap();
+
+    assert_eq!(size, NEW_BODY.len() as u64);
+    assert_eq!(fs::read(&dest).unwrap(), NEW_BODY);
+}
+
+#[test]
+fn starts_over_a_partial_file_from_another_url() {
+    let requests = Arc::new(Mutex::new(vec![]));
+    let server = range_server(requests.clone());
+    let dest = temp_dir("other-url").join("track.mp3");
+    fs::write(http_download::part_path(&dest), b"other song").unwrap();
+    Partial {
+        url: format!("{}/other.mp3", server.url()),
+        validator: Some(ETAG.to_string()),
+    }
+    .save(&dest)
+    .unwrap();
+
+    http_download::fetch(
+        &Request {
+            url: format!("{}/track.mp3", server.url()),
+            headers: vec![],
+        },
+        &dest,
+        |_, _| {},
+    )
+    .unwrap();
+
+    assert_eq!(fs::read(&dest).unwrap(), BODY);
+    let requests = requests.lock().unwrap();
+    assert!(!requests[0].iter().any(|(name, _)| name == "Range"));
+}
+
+#[test]
+fn reports_http_errors() {
+    let server = Server::new(|request: tiny_http::Request| {
+        request.respond(tiny_http::Response::new_empty(403.into()))
+    });
+    let dest = temp_dir("error").join("track.mp3");
+
+    let error = http_download::fetch(
+        &Request {
+            url: format!("{}/track.mp3", server.url()),
+            headers: vec![],
+        },
+        &dest,
+        |_, _| {},
+    )
+    .unwrap_err();
+
+    assert!(matches!(
+        error,
+        KvError::Download(DownloadError::Http(ref msg)) if msg.starts_with("403")
+    ));
+    assert!(!dest.exists());
+}
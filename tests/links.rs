@@ -0,0 +1,71 @@
+use std::collections::BTreeMap;
+
+use kv_downloader::links::{self, TrackLink};
+
+fn track_links() -> Vec<TrackLink> {
+    let headers = BTreeMap::from([
+        ("Cookie".to_string(), "karaoke-version=abc".to_string()),
+        ("User-Agent".to_string(), "Mozilla/5.0".to_string()),
+    ]);
+    vec![
+        TrackLink {
+            track: "Drum Kit".to_string(),
+            filename: "Cherub_Rock(Drum Kit).mp3".to_string(),
+            url: "https://www.karaoke-version.com/dl/1/Cherub_Rock%28Drum%20Kit%29.mp3".to_string(),
+            headers: headers.clone(),
+        },
+        TrackLink {
+            track: "Bass".to_string(),
+            filename: "Cherub_Rock(Bass).mp3".to_string(),
+            url: "https://www.karaoke-version.com/dl/2/Cherub_Rock%28Bass%29.mp3".to_string(),
+            headers,
+        },
+    ]
+}
+
+#[test]
+fn writes_json() {
+    let json = links::to_json("https://www.karaoke-version.com/song", &track_links()).unwrap();
+    let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+
+    assert_eq!(value["song"], "https://www.karaoke-version.com/song");
+    assert_eq!(value["tracks"][1]["track"], "Bass");
+    assert_eq!(value["tracks"][1]["filename"], "Cherub_Rock(Bass).mp3");
+    assert_eq!(
+        value["tracks"][0]["headers"]["Cookie"],
+        "karaoke-version=abc"
+    );
+}
+
+#[test]
+fn writes_an_aria2_input_file() {
+    let aria2 = links::to_aria2(&track_links()[..1], Some("/music"));
+
+    assert_eq!(
+        aria2,
+        "https://www.karaoke-version.com/dl/1/Cherub_Rock%28Drum%20Kit%29.mp3
+  out=Cherub_Rock(Drum Kit).mp3
+  dir=/music
+  header=Cookie: karaoke-version=abc
+  header=User-Agent: Mozilla/5.0
+"
+    );
+}
+
+#[test]
+fn writes_json_for_several_songs() {
+    let songs = vec![links::SongLinks {
+        song: "https://www.karaoke-version.com/song".to_string(),
+        dir: "/music/song".into(),
+        tracks: track_links(),
+    }];
+    let json = links::songs_to_json(&songs).unwrap();
+    let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+
+    assert_eq!(
+        value["songs"][0]["song"],
+        "https://www.karaoke-version.com/song"
+    );
+    assert_eq!(value["songs"][0]["tracks"][1]["track"], "Bass");
+    assert!(value["songs"][0].get("dir").is_none());
+}
@@ -0,0 +1,61 @@
+use kv_downloader::mixer::MixerState;
+
+fn names() -> Vec<String> {
+    ["Click", "Drum Kit", "Bass"]
+        .iter()
+        .map(|name| name.to_string())
+        .collect()
+}
+
+#[test]
+fn only_the_soloed_track_is_audible() {
+    let state = MixerState {
+        soloed: vec![false, true, false],
+        muted: vec![false, false, false],
+    };
+
+    assert_eq!(state.audible(), vec![1]);
+    assert!(state.is_solo(1));
+    assert!(!state.is_reset());
+}
+
+#[test]
+fn notices_a_solo_left_behind() {
+    let state = MixerState {
+        soloed: vec![false, true, true],
+        muted: vec![false, false, false],
+    };
+
+    assert!(!state.is_solo(2));
+    assert_eq!(
+        state.describe(&names()),
+        "soloed: Drum Kit, Bass; muted: none"
+    );
+}
+
+#[test]
+fn a_muted_solo_is_not_audible() {
+    let state = MixerState {
+        soloed: vec![false, false, true],
+        muted: vec![false, false, true],
+    };
+
+    assert!(state.audible().is_empty());
+    assert!(!state.is_solo(2));
+}
+
+#[test]
+fn everything_is_audible_without_a_solo() {
+    let state = MixerState {
+        soloed: vec![false, false, false],
+        muted: vec![true, false, false],
+    };
+
+    assert_eq!(state.audible(), vec![1, 2]);
+    assert_eq!(state.describe(&names()), "soloed: none; muted: Click");
+    assert!(MixerState {
+        soloed: vec![false; 3],
+        muted: vec![false; 3],
+    }
+    .is_reset());
+}
@@ -0,0 +1,142 @@
+use indicatif::ProgressDrawTarget;
+use kv_downloader::events::Event;
+use kv_downloader::observer::Observer;
+use kv_downloader::progress_view::{ProgressView, TrackState};
+
+const SONG: &str =
+    "https://www.karaoke-version.com/custombackingtrack/the-smashing-pumpkins/cherub-rock.html";
+
+#[test]
+fn follows_a_track_through_every_state() {
+    let view = ProgressView::new(ProgressDrawTarget::hidden(), 2);
+
+    view.track(SONG, "Bass", 1, TrackState::Queued);
+    view.track(SONG, "Drum Kit", 1, TrackState::Queued);
+    assert_eq!(view.overall(), (0, 2));
+
+    view.track(SONG, "Bass", 1, TrackState::Generating);
+    view.track(
+        SONG,
+        "Bass",
+        1,
+        TrackState::Downloading {
+            received: 512,
+            total: Some(1024),
+        },
+    );
+    view.track(SONG, "Bass", 1, TrackState::Failed("timed out".to_string()));
+    let (state, message) = view.track_status(SONG, "Bass").unwrap();
+    assert_eq!(state, TrackState::Failed("timed out".to_string()));
+    assert_eq!(message, "failed: timed out");
+    assert_eq!(view.overall(), (1, 2));
+
+    view.track(
+        SONG,
+        "Drum Kit",
+        2,
+        TrackState::Downloading {
+            received: 512,
+            total: None,
+        },
+    );
+    view.track(SONG, "Drum Kit", 2, TrackState::Verifying);
+    assert_eq!(
+        view.track_status(SONG, "Drum Kit").unwrap().1,
+        "verifying (retry 1)"
+    );
+    view.track(SONG, "Drum Kit", 2, TrackState::Done);
+    assert_eq!(
+        view.track_status(SONG, "Drum Kit").unwrap().1,
+        "done (retry 1)"
+    );
+    assert_eq!(view.overall(), (2, 2));
+    view.finish();
+}
+
+#[test]
+fn follows_the_events_of_a_run() {
+    let view = ProgressView::new(ProgressDrawTarget::hidden(), 1);
+    let song = SONG.to_string();
+    let track = "Bass".to_string();
+
+    view.on_event(&Event::SongLoaded {
+        song: song.clone(),
+        title: None,
+        tracks: vec![track.clone(), "Click".to_string()],
+        already_downloaded: vec!["Click".to_string()],
+    });
+    // the skipped track isn't shown, let alone queued
+    assert!(view.track_status(SONG, "Click").is_none());
+    assert_eq!(view.overall(), (0, 1));
+    assert_eq!(
+        view.track_status(SONG, "Bass").unwrap().0,
+        TrackState::Queued
+    );
+
+    view.on_event(&Event::TrackStarted {
+        song: song.clone(),
+        track: track.clone(),
+        attempt: 1,
+    });
+    view.on_event(&Event::TrackFailed {
+        song: song.clone(),
+        track: track.clone(),
+        attempt: 1,
+        error: "timed out".to_string(),
+        retrying: true,
+    });
+    assert_eq!(
+        view.track_status(SONG, "Bass").unwrap(),
+        (TrackState::Queued, "queued (retry 1)".to_string())
+    );
+    assert_eq!(view.overall(), (0, 1));
+
+    view.on_event(&Event::RateLimited {
+        song: song.clone(),
+        track: track.clone(),
+        until: "2026-10-19T14:05:00+02:00".to_string(),
+        reason: "hourly limit of 2 tracks reached".to_string(),
+    });
+    assert_eq!(
+        view.track_status(SONG, "Bass").unwrap().1,
+        "paused until 14:05 (hourly limit of 2 tracks reached) (retry 1)"
+    );
+    // the track starts once the pause is over
+    view.on_event(&Event::TrackStarted {
+        song: song.clone(),
+        track: track.clone(),
+        attempt: 2,
+    });
+    assert_eq!(
+        view.track_status(SONG, "Bass").unwrap().0,
+        TrackState::Generating
+    );
+    view.on_event(&Event::TrackProgress {
+        song: song.clone(),
+        track: track.clone(),
+        received: 1024,
+        total: Some(1024),
+    });
+    assert_eq!(
+        view.track_status(SONG, "Bass").unwrap().0,
+        TrackState::Downloading {
+            received: 1024,
+            total: Some(1024)
+        }
+    );
+    view.on_event(&Event::TrackVerifying {
+        song: song.clone(),
+        track: track.clone(),
+    });
+    view.on_event(&Event::TrackCompleted {
+        song,
+        track,
+        path: None,
+    });
+    assert_eq!(
+        view.track_status(SONG, "Bass").unwrap(),
+        (TrackState::Done, "done (retry 1)".to_string())
+    );
+    assert_eq!(view.overall(), (1, 1));
+    view.finish();
+}
@@ -0,0 +1,67 @@
+use std::num::NonZeroU32;
+use std::time::Duration;
+
+use kv_downloader::rate_limit::{RateLimits, Usage};
+
+const MINUTE: u64 = 60 * 1000;
+const HOUR: u64 = 60 * MINUTE;
+
+#[test]
+fn waits_between_tracks() {
+    let limits = RateLimits {
+        min_delay: Duration::from_secs(10),
+        ..Default::default()
+    };
+    let usage = Usage {
+        generations: vec![100_000],
+    };
+
+    let (wait, _) = usage.wait_time(&limits, 104_000).unwrap();
+    assert_eq!(wait, Duration::from_secs(6));
+    assert!(usage.wait_time(&limits, 110_000).is_none());
+}
+
+#[test]
+fn pauses_once_the_hourly_cap_is_reached() {
+    let limits = RateLimits {
+        per_hour: NonZeroU32::new(2),
+        ..Default::default()
+    };
+    let start = 10 * HOUR;
+    let usage = Usage {
+        generations: vec![start, start + 10 * MINUTE],
+    };
+
+    let (wait, reason) = usage.wait_time(&limits, start + 20 * MINUTE).unwrap();
+    assert_eq!(wait, Duration::from_secs(40 * 60));
+    assert!(reason.contains("hourly limit of 2"));
+
+    assert!(usage.wait_time(&limits, start + HOUR).is_none());
+}
+
+#[test]
+fn reports_the_longest_wait() {
+    let limits = RateLimits {
+        min_delay: Duration::from_secs(30),
+        per_hour: NonZeroU32::new(10),
+        per_day: NonZeroU32::new(2),
+    };
+    let start = 10 * HOUR;
+    let usage = Usage {
+        generations: vec![start, start + HOUR],
+    };
+
+    let (wait, reason) = usage.wait_time(&limits, start + HOUR).unwrap();
+    assert_eq!(wait, Duration::from_secs(23 * 60 * 60));
+    assert!(reason.contains("daily"));
+}
+
+#[test]
+fn forgets_usage_older_than_a_day() {
+    let mut usage = Usage {
+        generations: vec![HOUR, 20 * HOUR, 30 * HOUR],
+    };
+    usage.prune(26 * HOUR);
+
+    assert_eq!(usage.generations, vec![20 * HOUR, 30 * HOUR]);
+}
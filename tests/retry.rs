@@ -0,0 +1,76 @@
+use std::time::Duration;
+
+use kv_downloader::error::KvError;
+use kv_downloader::retry::RetryPolicy;
+use kv_downloader::tasks::download_song::DownloadError;
+
+#[test]
+fn backs_off_exponentially_up_to_a_limit() {
+    let policy = RetryPolicy {
+        backoff: Duration::from_secs(5),
+        max_backoff: Duration::from_secs(30),
+        ..Default::default()
+    };
+
+    assert_eq!(policy.backoff(1), Duration::from_secs(5));
+    assert_eq!(policy.backoff(2), Duration::from_secs(10));
+    assert_eq!(policy.backoff(3), Duration::from_secs(20));
+    assert_eq!(policy.backoff(4), Duration::from_secs(30));
+    assert_eq!(policy.backoff(100), Duration::from_secs(30));
+}
+
+#[test]
+fn adds_jitter_within_bounds() {
+    let policy = RetryPolicy {
+        backoff: Duration::from_secs(10),
+        jitter: 0.5,
+        ..Default::default()
+    };
+
+    assert_eq!(policy.backoff_with_jitter(1, 0.0), Duration::from_secs(10));
+    assert_eq!(
+        policy.backoff_with_jitter(1, 0.5),
+        Duration::from_millis(12500)
+    );
+    assert!(policy.backoff_with_jitter(1, 0.999) < Duration::from_secs(15));
+}
+
+#[test]
+fn extends_the_modal_timeout_on_each_retry() {
+    let policy = RetryPolicy::default();
+
+    assert_eq!(policy.modal_timeout(1), Duration::from_secs(60));
+    assert_eq!(policy.modal_timeout(3), Duration::from_secs(120));
+}
+
+#[test]
+fn classifies_errors_for_retrying() {
+    let retryable = [
+        KvError::Download(DownloadError::ModalTimeout(Duration::from_secs(60))),
+        KvError::Download(DownloadError::CompletionTimeout(Duration::from_secs(300))),
+        KvError::Navigation {
+            url: "https://www.karaoke-version.com".to_string(),
+            source: anyhow::anyhow!("timed out"),
+        },
+        KvError::Diagnosed {
+            error: Box::new(KvError::SelectorMissing(".begin-download".to_string())),
+            diagnostics: "kv-diagnostics/x".into(),
+        },
+    ];
+    for error in &retryable {
+        assert!(error.is_retryable(), "{} should be retried", error);
+    }
+
+    let fatal = [
+        KvError::Download(DownloadError::NotPurchased),
+        KvError::Download(DownloadError::HumanVerificationRequired),
+        KvError::Auth("bad password".to_string()),
+        KvError::Diagnosed {
+            error: Box::new(KvError::Download(DownloadError::NotPurchased)),
+            diagnostics: "kv-diagnostics/x".into(),
+        },
+    ];
+    for error in &fatal {
+        assert!(!error.is_retryable(), "{} should not be retried", error);
+    }
+}
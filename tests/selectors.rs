@@ -0,0 +1,17 @@
+use kv_downloader::selectors::Selectors;
+
+#[test]
+fn overrides_keep_defaults_for_the_rest() {
+    let selectors = Selectors::parse(r#"solo_button = ".track .btn--solo""#).unwrap();
+
+    assert_eq!(selectors.solo_button, ".track .btn--solo");
+    assert_eq!(
+        selectors.download_button,
+        Selectors::default().download_button
+    );
+}
+
+#[test]
+fn rejects_unknown_selectors() {
+    assert!(Selectors::parse(r#"solo_buton = ".track .btn--solo""#).is_err());
+}
@@ -0,0 +1,55 @@
+use kv_downloader::session::{cookie_matches_domain, Session, SessionCookie};
+
+fn cookie(name: &str, expires: f64) -> SessionCookie {
+    SessionCookie {
+        name: name.to_string(),
+        value: "value".to_string(),
+        domain: ".karaoke-version.com".to_string(),
+        path: "/".to_string(),
+        expires,
+        secure: true,
+        http_only: true,
+        same_site: None,
+    }
+}
+
+#[test]
+fn drops_expired_cookies() {
+    let session = Session {
+        cookies: vec![
+            cookie("expired", 1_000.0),
+            cookie("session", -1.0),
+            cookie("future", 32_503_680_000.0),
+        ],
+        ..Default::default()
+    };
+
+    let names: Vec<String> = session
+        .without_expired()
+        .cookies
+        .into_iter()
+        .map(|c| c.name)
+        .collect();
+
+    assert_eq!(names, vec!["session".to_string(), "future".to_string()]);
+}
+
+#[test]
+fn matches_cookie_domains() {
+    assert!(cookie_matches_domain(
+        ".karaoke-version.com",
+        "www.karaoke-version.com"
+    ));
+    assert!(cookie_matches_domain(
+        "www.karaoke-version.com",
+        "www.karaoke-version.com"
+    ));
+    assert!(!cookie_matches_domain(
+        ".karaoke-version.co.uk",
+        "www.karaoke-version.com"
+    ));
+    assert!(!cookie_matches_domain(
+        "version.com",
+        "www.karaoke-version.com"
+    ));
+}
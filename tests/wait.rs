@@ -0,0 +1,39 @@
+use std::time::{Duration, Instant};
+
+use kv_downloader::wait::poll;
+
+const INTERVAL: Duration = Duration::from_millis(5);
+
+#[test]
+fn returns_as_soon_as_the_condition_holds() {
+    let mut checks = 0;
+    let held = poll::<()>(Duration::from_secs(5), INTERVAL, || {
+        checks += 1;
+        Ok(checks == 3)
+    });
+
+    assert_eq!(held, Ok(true));
+    assert_eq!(checks, 3);
+}
+
+#[test]
+fn gives_up_after_the_timeout() {
+    let start = Instant::now();
+    let held = poll::<()>(Duration::from_millis(50), INTERVAL, || Ok(false));
+
+    assert_eq!(held, Ok(false));
+    assert!(start.elapsed() >= Duration::from_millis(50));
+    assert!(start.elapsed() < Duration::from_secs(1));
+}
+
+#[test]
+fn stops_on_the_first_error() {
+    let mut checks = 0;
+    let held = poll(Duration::from_secs(5), INTERVAL, || {
+        checks += 1;
+        Err("gone")
+    });
+
+    assert_eq!(held, Err("gone"));
+    assert_eq!(checks, 1);
+}